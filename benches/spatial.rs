@@ -0,0 +1,86 @@
+#[macro_use]
+extern crate criterion;
+extern crate bws;
+
+use bws::simulation::{spatial::SpatialIndex, FlockId};
+use criterion::{black_box, BenchmarkId, Criterion};
+
+const RADIUS: f64 = 0.01;
+
+fn points(n: usize) -> Vec<(f64, f64)> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    (0..n).map(|_| (next(), next())).collect()
+}
+
+fn index_of(points: &[(f64, f64)]) -> SpatialIndex {
+    let mut index = SpatialIndex::default();
+    let team = index.add_team("bench");
+    for (id, &(x, y)) in points.iter().enumerate() {
+        index.insert(team, FlockId::from(id as u64), x, y);
+    }
+    index
+}
+
+fn rebuild(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rebuild");
+    for &n in &[1_000, 10_000, 50_000] {
+        let points = points(n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &points, |b, points| {
+            b.iter(|| index_of(black_box(points)))
+        });
+    }
+    group.finish();
+}
+
+fn neighbours(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbours_within");
+    group.sample_size(10);
+    for &n in &[1_000, 10_000, 50_000] {
+        let points = points(n);
+        let index = index_of(&points);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &points, |b, points| {
+            b.iter(|| {
+                points
+                    .iter()
+                    .map(|&(x, y)| index.neighbours_within(x, y, RADIUS).len())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn naive(c: &mut Criterion) {
+    let mut group = c.benchmark_group("naive");
+    group.sample_size(10);
+    for &n in &[1_000, 10_000, 50_000] {
+        let points = points(n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &points, |b, points| {
+            b.iter(|| {
+                points
+                    .iter()
+                    .map(|&(x, y)| {
+                        points
+                            .iter()
+                            .filter(|&&(ox, oy)| {
+                                let dx = (ox - x).abs().min(1f64 - (ox - x).abs());
+                                let dy = (oy - y).abs().min(1f64 - (oy - y).abs());
+                                dx * dx + dy * dy <= RADIUS * RADIUS
+                            })
+                            .count()
+                    })
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rebuild, neighbours, naive);
+criterion_main!(benches);
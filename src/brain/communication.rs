@@ -2,4 +2,5 @@ use hyper::Uri;
 
 pub enum Message {
     Pick(Vec<(String, Uri, String)>),
+    Stop,
 }
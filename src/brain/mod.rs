@@ -1,6 +1,9 @@
 pub mod communication;
 
-use std::sync::mpsc::{Receiver, Sender};
+use std::{
+    sync::mpsc::{Receiver, Sender},
+    time::Instant,
+};
 
 use futures::{stream::Stream, Future};
 use hyper::{header::ContentType, Client, Method, Request};
@@ -9,6 +12,8 @@ use tokio_core::reactor::Core;
 use self::communication::Message as BrainMessage;
 use crate::simulation::{communication::Message as SimulationMessage, Intentions};
 
+const EXCERPT_LENGTH: usize = 200;
+
 pub struct Brain {
     rx: Receiver<BrainMessage>,
     tx: Sender<SimulationMessage>,
@@ -29,35 +34,50 @@ impl Brain {
                     BrainMessage::Pick(servers) => {
                         for (team_name, uri, payload) in servers {
                             info!("picking brain of {} at {}", team_name, uri);
-                            let (team_tx, success_team_name, failure_team_name) =
-                                (self.tx.clone(), team_name.clone(), team_name.clone());
+                            let (team_tx, failure_tx, success_team_name, failure_team_name) = (
+                                self.tx.clone(),
+                                self.tx.clone(),
+                                team_name.clone(),
+                                team_name.clone(),
+                            );
+                            let start = Instant::now();
                             let mut request = Request::new(Method::Post, uri);
                             request.headers_mut().set(ContentType::json());
                             request.set_body(payload);
                             let work = client
                                 .request(request)
-                                .and_then(|response| response.body().concat2())
-                                .map(|chunk| {
-                                    String::from_utf8(chunk.to_vec())
-                                        .map_err(|_| Error::DefunctInput)
+                                .and_then(|response| {
+                                    let status = response.status().as_u16();
+                                    response.body().concat2().map(move |chunk| (status, chunk))
                                 })
-                                .map(|source| {
-                                    source.and_then(|src| {
-                                        serde_json::from_str::<Intentions>(&src)
-                                            .map_err(|_| Error::CouldNotDeserialize)
-                                    })
+                                .map(move |(status, chunk)| {
+                                    interpret(status, &chunk, latency(start))
                                 })
-                                .map(move |result| {
-                                    if let Ok(intentions) = result {
+                                .map(move |result| match result {
+                                    Ok(intentions) => {
                                         info!("picked brain of {}", success_team_name);
-                                        team_tx
-                                            .send(SimulationMessage::BrainUpdate(
+                                        if let Err(error) =
+                                            team_tx.send(SimulationMessage::BrainUpdate(
                                                 success_team_name,
                                                 intentions,
                                             ))
-                                            .unwrap();
-                                    } else {
-                                        error!("could not read response of {}", failure_team_name);
+                                        {
+                                            error!("could not send brain update: {}", error);
+                                        }
+                                    }
+                                    Err(diagnostic) => {
+                                        error!(
+                                            "could not read response of {}: {:?}",
+                                            success_team_name, diagnostic.error
+                                        );
+                                        if let Err(error) =
+                                            team_tx.send(SimulationMessage::BrainError(
+                                                success_team_name,
+                                                diagnostic,
+                                            ))
+                                        {
+                                            error!("could not send brain error: {}", error);
+                                        }
                                     }
                                 })
                                 .map_err(move |error| {
@@ -65,6 +85,22 @@ impl Brain {
                                         "did not receive brain update from {}: {}",
                                         team_name, error
                                     );
+                                    let diagnostic = Diagnostic {
+                                        error: Error::Transport {
+                                            message: error.to_string(),
+                                        },
+                                        status: None,
+                                        excerpt: None,
+                                        latency: latency(start),
+                                    };
+                                    if let Err(error) =
+                                        failure_tx.send(SimulationMessage::BrainError(
+                                            failure_team_name,
+                                            diagnostic,
+                                        ))
+                                    {
+                                        error!("could not send brain error: {}", error);
+                                    }
                                 });
 
                             match core.run(work) {
@@ -72,15 +108,96 @@ impl Brain {
                             }
                         }
                     }
+                    BrainMessage::Stop => return,
                 }
             } else {
-                error!("could not receive message")
+                error!("could not receive message");
+                return;
             }
         }
     }
 }
 
+fn latency(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+fn interpret(status: u16, body: &[u8], latency: u64) -> Result<Intentions, Diagnostic> {
+    let excerpt: String = String::from_utf8_lossy(body)
+        .chars()
+        .take(EXCERPT_LENGTH)
+        .collect();
+    let diagnostic = |error| Diagnostic {
+        error,
+        status: Some(status),
+        excerpt: Some(excerpt.clone()),
+        latency,
+    };
+
+    if !(200..300).contains(&status) {
+        return Err(diagnostic(Error::Status));
+    }
+    let source = std::str::from_utf8(body).map_err(|_| diagnostic(Error::DefunctInput))?;
+    serde_json::from_str::<Intentions>(source).map_err(|error| {
+        diagnostic(Error::CouldNotDeserialize {
+            message: error.to_string(),
+            line: error.line(),
+            column: error.column(),
+        })
+    })
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub error: Error,
+    pub status: Option<u16>,
+    pub excerpt: Option<String>,
+    pub latency: u64,
+}
+
+impl Diagnostic {
+    /// Whether both diagnostics describe the same failure, whatever their latency.
+    pub fn same_failure(&self, other: &Diagnostic) -> bool {
+        self.error == other.error && self.status == other.status && self.excerpt == other.excerpt
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
 pub enum Error {
+    Transport {
+        message: String,
+    },
+    Status,
     DefunctInput,
-    CouldNotDeserialize,
+    CouldNotDeserialize {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_brain_responses_should_be_diagnosed() {
+        let body = b"{\n  \"1\": {\"heading\": }\n}";
+
+        let diagnostic = interpret(200, body, 12).unwrap_err();
+
+        match diagnostic.error {
+            Error::CouldNotDeserialize { line, .. } => assert_eq!(line, 2),
+            ref error => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(diagnostic.status, Some(200));
+        assert_eq!(
+            diagnostic.excerpt.as_deref(),
+            Some("{\n  \"1\": {\"heading\": }\n}")
+        );
+        assert_eq!(
+            interpret(500, b"oops", 12).unwrap_err().error,
+            Error::Status
+        );
+    }
 }
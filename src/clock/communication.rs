@@ -0,0 +1,8 @@
+use std::time::Duration;
+
+pub enum Message {
+    Pause,
+    Resume,
+    SetTick(Duration),
+    Stop,
+}
@@ -1,23 +1,85 @@
-use std::{sync::mpsc::Sender, thread, time::Duration};
+pub mod communication;
 
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use self::communication::Message as ClockMessage;
 use crate::simulation::communication::Message as SimulationMessage;
 
+/// Longest tick in milliseconds.
+pub const MAX_TICK: u64 = 10_000;
+
 pub struct Clock {
     tick_duration: Duration,
+    paused: bool,
+    rx: Receiver<ClockMessage>,
     tx: Sender<SimulationMessage>,
 }
 
 impl Clock {
-    pub fn new(tick_duration: Duration, tx: Sender<SimulationMessage>) -> Self {
-        Self { tick_duration, tx }
+    pub fn new(
+        tick_duration: Duration,
+        rx: Receiver<ClockMessage>,
+        tx: Sender<SimulationMessage>,
+    ) -> Self {
+        Self {
+            tick_duration,
+            paused: false,
+            rx,
+            tx,
+        }
+    }
+
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
     }
 
     pub fn start(&mut self) {
+        let mut next = Instant::now() + self.tick_duration;
         loop {
-            thread::sleep(self.tick_duration);
-            if let Err(error) = self.tx.send(SimulationMessage::Tick) {
-                error!("Could not send tick message: {}", error);
+            let remaining = next.saturating_duration_since(Instant::now());
+            match self.rx.recv_timeout(remaining) {
+                Ok(ClockMessage::Pause) => self.paused = true,
+                Ok(ClockMessage::Resume) => self.paused = false,
+                Ok(ClockMessage::SetTick(tick_duration)) => {
+                    self.tick_duration = tick_duration;
+                    next = Instant::now() + tick_duration;
+                }
+                Ok(ClockMessage::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                Err(RecvTimeoutError::Timeout) => {
+                    next = Instant::now() + self.tick_duration;
+                    if self.paused {
+                        continue;
+                    }
+                    if let Err(error) = self.tx.send(SimulationMessage::Tick) {
+                        error!("Could not send tick message: {}", error);
+                    }
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn clock_should_stop_without_waiting_for_the_tick() {
+        let (clock_tx, clock_rx) = channel();
+        let (simulation_tx, simulation_rx) = channel();
+        let mut clock = Clock::new(Duration::from_secs(3600), clock_rx, simulation_tx);
+        clock_tx.send(ClockMessage::Pause).unwrap();
+        clock_tx.send(ClockMessage::Stop).unwrap();
+
+        let start = Instant::now();
+        clock.start();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(simulation_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,922 @@
+use std::{fs, path::Path, time::Duration};
+
+use toml::{self, value::Table, Value};
+
+use crate::{
+    clock,
+    simulation::{
+        self,
+        arena::{self, Predation},
+        collision::{self, Collisions},
+        energy::Metabolism,
+        flag, food, leaderboard,
+        race::{self, Checkpoint, Tracking},
+        reproduction,
+        wind::Wind,
+    },
+    websocket,
+};
+
+/// Environment variables and the setting each of them overrides.
+const ENVIRONMENT: &[(&str, &str)] = &[
+    ("address", "server.address"),
+    ("socket", "server.socket"),
+    ("single_port", "server.single_port"),
+    ("websocket_path", "server.websocket_path"),
+    ("admin_token", "server.admin_token"),
+    ("tick", "clock.tick"),
+    ("heartbeat_sleep_duration", "heartbeat.sleep_duration"),
+    ("snapshot", "snapshot.path"),
+    ("snapshot_flocks", "snapshot.flocks"),
+    ("leaderboard_interval", "simulation.leaderboard_interval"),
+    ("leaderboard_history", "simulation.leaderboard_history"),
+    ("population_cap", "simulation.population_cap"),
+    ("keyframe_interval", "websocket.keyframe_interval"),
+    ("websocket_max_pending", "websocket.max_pending"),
+    ("websocket_max_dropped", "websocket.max_dropped"),
+    ("reply_timeout", "timeouts.reply"),
+    ("shutdown_timeout", "timeouts.shutdown"),
+    ("wind", "arena.wind"),
+    ("collision_radius", "arena.collisions.radius"),
+    ("collision_response", "arena.collisions.response"),
+    ("capture_radius", "arena.predation.capture_radius"),
+    ("food_spawn_rate", "arena.foraging.spawn_rate"),
+    ("food_max_patches", "arena.foraging.max_patches"),
+    ("food_patch_size", "arena.foraging.patch_size"),
+    ("food_patch_radius", "arena.foraging.patch_radius"),
+    ("food_regrowth", "arena.foraging.regrowth"),
+    ("food_offspring", "arena.foraging.offspring"),
+    ("flag_base_radius", "arena.capture_the_flag.base_radius"),
+    ("flag_tag_radius", "arena.capture_the_flag.tag_radius"),
+    ("energy_speed_cost", "arena.energy.speed_cost"),
+    ("energy_turn_cost", "arena.energy.turn_cost"),
+    ("energy_rest_regeneration", "arena.energy.rest_regeneration"),
+    ("energy_food", "arena.energy.food"),
+    ("max_age", "arena.energy.max_age"),
+    ("reproduction_rate", "arena.reproduction.rate"),
+    ("reproduction_energy", "arena.reproduction.energy"),
+    ("reproduction_mate_radius", "arena.reproduction.mate_radius"),
+    ("reproduction_mutation", "arena.reproduction.mutation"),
+    ("race_checkpoints", "arena.race.checkpoints"),
+    ("race_checkpoint_radius", "arena.race.checkpoint_radius"),
+    ("race_tracking", "arena.race.tracking"),
+    ("race_finishers", "arena.race.finishers"),
+];
+
+/// Settings that switch their rule on. Every other setting of a rule that is off is an error.
+const ENABLING: &[&str] = &[
+    "arena.collisions.radius",
+    "arena.predation.capture_radius",
+    "arena.foraging.spawn_rate",
+    "arena.reproduction.rate",
+    "arena.race.checkpoints",
+];
+
+/// Environment variables that switch a rule with its default settings on or off.
+const SWITCHES: &[(&str, &str)] = &[
+    ("capture_the_flag", "arena.capture_the_flag"),
+    ("energy", "arena.energy"),
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: Server,
+    pub clock: Clock,
+    pub heartbeat: Heartbeat,
+    pub snapshot: Snapshot,
+    pub simulation: Simulation,
+    pub websocket: Websocket,
+    pub timeouts: Timeouts,
+    pub arena: Arena,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Server {
+    pub address: String,
+    pub socket: String,
+    pub single_port: bool,
+    pub websocket_path: String,
+    pub admin_token: Option<String>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:2643".to_string(),
+            socket: "127.0.0.1:3435".to_string(),
+            single_port: true,
+            websocket_path: "/ws".to_string(),
+            admin_token: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Clock {
+    /// Milliseconds between ticks.
+    pub tick: u64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self { tick: 33 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Heartbeat {
+    /// Seconds between heartbeats.
+    pub sleep_duration: u64,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self { sleep_duration: 15 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Snapshot {
+    pub path: String,
+    pub flocks: bool,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self {
+            path: "snapshot.json".to_string(),
+            flocks: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Simulation {
+    pub leaderboard_interval: u64,
+    /// Leaderboard samples kept per team.
+    pub leaderboard_history: usize,
+    /// Most boids a team can have.
+    pub population_cap: usize,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            leaderboard_interval: simulation::LEADERBOARD_INTERVAL,
+            leaderboard_history: leaderboard::HISTORY_LENGTH,
+            population_cap: simulation::POPULATION_CAP,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Websocket {
+    pub keyframe_interval: u64,
+    pub max_pending: u64,
+    pub max_dropped: u64,
+}
+
+impl Default for Websocket {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: websocket::KEYFRAME_INTERVAL,
+            max_pending: websocket::MAX_PENDING,
+            max_dropped: websocket::MAX_DROPPED,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// Milliseconds to wait for the simulation to answer a request.
+    pub reply: u64,
+    /// Milliseconds to wait for each subsystem to stop.
+    pub shutdown: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            reply: 1000,
+            shutdown: 5000,
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn reply(&self) -> Duration {
+        Duration::from_millis(self.reply)
+    }
+
+    pub fn shutdown(&self) -> Duration {
+        Duration::from_millis(self.shutdown)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Arena {
+    pub wind: Option<String>,
+    pub collisions: Option<CollisionRule>,
+    pub predation: Option<PredationRule>,
+    pub foraging: Option<ForagingRule>,
+    pub capture_the_flag: Option<CaptureTheFlagRule>,
+    pub energy: Option<EnergyRule>,
+    pub reproduction: Option<ReproductionRule>,
+    pub race: Option<RaceRule>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollisionRule {
+    pub radius: f64,
+    pub response: String,
+}
+
+impl Default for CollisionRule {
+    fn default() -> Self {
+        Self {
+            radius: 0.01,
+            response: "push".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PredationRule {
+    pub capture_radius: f64,
+}
+
+impl Default for PredationRule {
+    fn default() -> Self {
+        Self {
+            capture_radius: 0.02,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForagingRule {
+    pub spawn_rate: f64,
+    pub max_patches: usize,
+    pub patch_size: f64,
+    pub patch_radius: f64,
+    pub regrowth: f64,
+    pub offspring: usize,
+}
+
+impl Default for ForagingRule {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 0.01,
+            max_patches: 10,
+            patch_size: 10f64,
+            patch_radius: 0.02,
+            regrowth: 0f64,
+            offspring: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureTheFlagRule {
+    pub base_radius: f64,
+    pub tag_radius: f64,
+}
+
+impl Default for CaptureTheFlagRule {
+    fn default() -> Self {
+        Self {
+            base_radius: 0.05,
+            tag_radius: 0.02,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnergyRule {
+    pub speed_cost: f64,
+    pub turn_cost: f64,
+    pub rest_regeneration: f64,
+    pub food: f64,
+    pub max_age: Option<u64>,
+}
+
+impl Default for EnergyRule {
+    fn default() -> Self {
+        Self {
+            speed_cost: 0.1,
+            turn_cost: 0.001,
+            rest_regeneration: 0.002,
+            food: 0.25,
+            max_age: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReproductionRule {
+    pub rate: f64,
+    pub energy: Option<f64>,
+    pub mate_radius: Option<f64>,
+    pub mutation: f64,
+}
+
+impl Default for ReproductionRule {
+    fn default() -> Self {
+        Self {
+            rate: 0.001,
+            energy: None,
+            mate_radius: None,
+            mutation: 0f64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RaceRule {
+    /// Positions like `0.2,0.2;0.8,0.2`.
+    pub checkpoints: String,
+    pub checkpoint_radius: f64,
+    pub tracking: String,
+    pub finishers: usize,
+}
+
+impl Default for RaceRule {
+    fn default() -> Self {
+        Self {
+            checkpoints: String::new(),
+            checkpoint_radius: 0.05,
+            tracking: "boids".to_string(),
+            finishers: 1,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the defaults, overlaid by the file, the environment and the overrides, in that order.
+    pub fn load<F>(
+        file: Option<&Path>,
+        environment: F,
+        overrides: &[(String, String)],
+    ) -> Result<Self, Vec<String>>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut value =
+            Value::try_from(Config::default()).map_err(|error| vec![error.to_string()])?;
+        if let Some(file) = file {
+            let source = fs::read_to_string(file)
+                .map_err(|error| vec![format!("could not read {}: {}", file.display(), error)])?;
+            let contents: Value = toml::from_str(&source)
+                .map_err(|error| vec![format!("could not parse {}: {}", file.display(), error)])?;
+            merge(&mut value, contents);
+        }
+
+        let mut errors = Vec::new();
+        for (key, path) in SWITCHES {
+            if let Some(switch) = environment(key) {
+                match switch.parse::<bool>() {
+                    Ok(true) => enable(&mut value, path),
+                    Ok(false) => disable(&mut value, path),
+                    Err(_) => errors.push(format!("\"{}\" should be true or false", key)),
+                }
+            }
+        }
+        for (key, path) in ENVIRONMENT {
+            if let Some(setting) = environment(key) {
+                if let Err(error) = set(&mut value, path, &setting) {
+                    errors.push(format!("\"{}\": {}", key, error));
+                }
+            }
+        }
+        let (enabling, others): (Vec<_>, Vec<_>) = overrides
+            .iter()
+            .partition(|(path, _)| ENABLING.contains(&path.as_str()));
+        for (path, setting) in enabling.into_iter().chain(others) {
+            if let Err(error) = set(&mut value, path, setting) {
+                errors.push(error);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let config: Config = value.try_into().map_err(|error| vec![error.to_string()])?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, message: &str| {
+            if !valid {
+                errors.push(message.to_string());
+            }
+        };
+        check(
+            (1..=clock::MAX_TICK).contains(&self.clock.tick),
+            &format!(
+                "clock.tick should be between 1 and {} milliseconds",
+                clock::MAX_TICK
+            ),
+        );
+        check(
+            self.heartbeat.sleep_duration > 0,
+            "heartbeat.sleep_duration should be at least 1 second",
+        );
+        check(
+            self.simulation.leaderboard_interval > 0,
+            "simulation.leaderboard_interval should be at least 1",
+        );
+        check(
+            self.simulation.leaderboard_history > 0,
+            "simulation.leaderboard_history should be at least 1",
+        );
+        check(
+            self.websocket.keyframe_interval > 0,
+            "websocket.keyframe_interval should be at least 1",
+        );
+        check(
+            self.websocket.max_pending > 0,
+            "websocket.max_pending should be at least 1",
+        );
+        check(
+            self.timeouts.reply > 0,
+            "timeouts.reply should be at least 1 millisecond",
+        );
+        check(
+            self.server.websocket_path.starts_with('/'),
+            "server.websocket_path should start with /",
+        );
+        check(
+            self.server.single_port || self.socket_port().is_some(),
+            "server.socket should end with a port",
+        );
+        let arena = &self.arena;
+        if let Some(rule) = &arena.collisions {
+            check(
+                positive(rule.radius),
+                "arena.collisions.radius should be positive",
+            );
+        }
+        if let Some(rule) = &arena.predation {
+            check(
+                positive(rule.capture_radius),
+                "arena.predation.capture_radius should be positive",
+            );
+        }
+        if let Some(rule) = &arena.foraging {
+            check(
+                probability(rule.spawn_rate),
+                "arena.foraging.spawn_rate should be above 0 and at most 1",
+            );
+            check(
+                positive(rule.patch_size),
+                "arena.foraging.patch_size should be positive",
+            );
+            check(
+                positive(rule.patch_radius),
+                "arena.foraging.patch_radius should be positive",
+            );
+            check(
+                non_negative(rule.regrowth),
+                "arena.foraging.regrowth should not be negative",
+            );
+        }
+        if let Some(rule) = &arena.capture_the_flag {
+            check(
+                positive(rule.base_radius),
+                "arena.capture_the_flag.base_radius should be positive",
+            );
+            check(
+                positive(rule.tag_radius),
+                "arena.capture_the_flag.tag_radius should be positive",
+            );
+        }
+        if let Some(rule) = &arena.energy {
+            check(
+                non_negative(rule.speed_cost),
+                "arena.energy.speed_cost should not be negative",
+            );
+            check(
+                non_negative(rule.turn_cost),
+                "arena.energy.turn_cost should not be negative",
+            );
+            check(
+                non_negative(rule.rest_regeneration),
+                "arena.energy.rest_regeneration should not be negative",
+            );
+            check(
+                non_negative(rule.food),
+                "arena.energy.food should not be negative",
+            );
+            check(
+                rule.max_age != Some(0),
+                "arena.energy.max_age should be at least 1",
+            );
+        }
+        if let Some(rule) = &arena.reproduction {
+            check(
+                probability(rule.rate),
+                "arena.reproduction.rate should be above 0 and at most 1",
+            );
+            check(
+                rule.energy
+                    .is_none_or(|energy| (0f64..=1f64).contains(&energy)),
+                "arena.reproduction.energy should be between 0 and 1",
+            );
+            check(
+                rule.mate_radius.is_none_or(positive),
+                "arena.reproduction.mate_radius should be positive",
+            );
+            check(
+                (0f64..=1f64).contains(&rule.mutation),
+                "arena.reproduction.mutation should be between 0 and 1",
+            );
+        }
+        if let Some(rule) = &arena.race {
+            check(
+                positive(rule.checkpoint_radius),
+                "arena.race.checkpoint_radius should be positive",
+            );
+            check(
+                rule.finishers > 0,
+                "arena.race.finishers should be at least 1",
+            );
+        }
+        if let Err(error) = self.arena.build() {
+            errors.push(error);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn socket_port(&self) -> Option<u16> {
+        self.server
+            .socket
+            .rsplit(':')
+            .next()
+            .and_then(|port| port.parse().ok())
+    }
+}
+
+impl Arena {
+    pub fn build(&self) -> Result<arena::Arena, String> {
+        let mut arena = arena::Arena::new();
+        if let Some(wind) = &self.wind {
+            arena.wind = Some(
+                wind.parse::<Wind>()
+                    .map_err(|error| format!("arena.wind: {}", error))?,
+            );
+        }
+        if let Some(rule) = &self.collisions {
+            let response = rule
+                .response
+                .parse::<collision::Response>()
+                .map_err(|error| format!("arena.collisions.response: {}", error))?;
+            arena.collisions = Some(Collisions::new(rule.radius, response));
+        }
+        arena.predation = self
+            .predation
+            .as_ref()
+            .map(|rule| Predation::new(rule.capture_radius));
+        arena.foraging = self.foraging.as_ref().map(|rule| {
+            food::Foraging::new(rule.spawn_rate)
+                .max_patches(rule.max_patches)
+                .patch_size(rule.patch_size)
+                .patch_radius(rule.patch_radius)
+                .regrowth(rule.regrowth)
+                .offspring(rule.offspring)
+        });
+        arena.capture_the_flag = self
+            .capture_the_flag
+            .as_ref()
+            .map(|rule| flag::CaptureTheFlag::new(rule.base_radius, rule.tag_radius));
+        arena.metabolism = self.energy.as_ref().map(|rule| {
+            Metabolism::new()
+                .speed_cost(rule.speed_cost)
+                .turn_cost(rule.turn_cost)
+                .rest_regeneration(rule.rest_regeneration)
+                .food_energy(rule.food)
+                .max_age(rule.max_age)
+        });
+        arena.reproduction = self.reproduction.as_ref().map(|rule| {
+            let energy_threshold = rule.energy.or_else(|| self.energy.as_ref().map(|_| 0.8));
+            reproduction::Reproduction::new(rule.rate)
+                .energy_threshold(energy_threshold)
+                .mate_radius(rule.mate_radius)
+                .mutation(rule.mutation)
+        });
+        if let Some(rule) = &self.race {
+            let tracking = rule
+                .tracking
+                .parse::<Tracking>()
+                .map_err(|error| format!("arena.race.tracking: {}", error))?;
+            let checkpoints = checkpoints(&rule.checkpoints, rule.checkpoint_radius)?;
+            arena.race = Some(race::Race::new(checkpoints, tracking, rule.finishers));
+        }
+        Ok(arena)
+    }
+}
+
+fn checkpoints(representation: &str, radius: f64) -> Result<Vec<Checkpoint>, String> {
+    let error = || {
+        format!(
+            "arena.race.checkpoints should be x,y;x,y;... but is \"{}\"",
+            representation
+        )
+    };
+    representation
+        .split(';')
+        .map(|checkpoint| {
+            let coordinates = checkpoint
+                .split(',')
+                .map(|coordinate| coordinate.trim().parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|_| error())?;
+            match coordinates.as_slice() {
+                [x, y] => Ok(Checkpoint::new(*x, *y, radius)),
+                _ => Err(error()),
+            }
+        })
+        .collect()
+}
+
+/// Parses a command line into the path of a configuration file, the flags and the overrides.
+pub struct Arguments {
+    pub config: Option<String>,
+    pub restore: bool,
+    pub print_config: bool,
+    pub overrides: Vec<(String, String)>,
+}
+
+impl Arguments {
+    pub fn parse<I>(arguments: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parsed = Arguments {
+            config: None,
+            restore: false,
+            print_config: false,
+            overrides: Vec::new(),
+        };
+        let mut arguments = arguments.into_iter();
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--restore" => parsed.restore = true,
+                "--print-config" => parsed.print_config = true,
+                "--config" => {
+                    parsed.config = Some(
+                        arguments
+                            .next()
+                            .ok_or_else(|| "--config should be followed by a file".to_string())?,
+                    )
+                }
+                _ => {
+                    let setting = argument
+                        .strip_prefix("--")
+                        .ok_or_else(|| format!("unknown argument \"{}\"", argument))?;
+                    let (path, value) = match setting.split_once('=') {
+                        Some((path, value)) => (path.to_string(), value.to_string()),
+                        None => (
+                            setting.to_string(),
+                            arguments.next().ok_or_else(|| {
+                                format!("{} should be followed by a value", argument)
+                            })?,
+                        ),
+                    };
+                    parsed.overrides.push((path, value));
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn table_at<'a>(value: &'a mut Value, path: &[&str]) -> &'a mut Table {
+    if !value.is_table() {
+        *value = Value::Table(Table::new());
+    }
+    let table = match value {
+        Value::Table(table) => table,
+        _ => unreachable!(),
+    };
+    match path.split_first() {
+        Some((key, rest)) => table_at(
+            table
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Table(Table::new())),
+            rest,
+        ),
+        None => table,
+    }
+}
+
+fn enable(value: &mut Value, path: &str) {
+    let keys: Vec<&str> = path.split('.').collect();
+    table_at(value, &keys);
+}
+
+fn disable(value: &mut Value, path: &str) {
+    let keys: Vec<&str> = path.split('.').collect();
+    if let Some((last, parents)) = keys.split_last() {
+        table_at(value, parents).remove(*last);
+    }
+}
+
+/// Sets a single setting, reading the value with the type of the setting it replaces.
+fn set(value: &mut Value, path: &str, setting: &str) -> Result<(), String> {
+    let keys: Vec<&str> = path.split('.').collect();
+    if let Some((last, parents)) = keys.split_last() {
+        let exists = parents
+            .iter()
+            .try_fold(&*value, |value, key| value.get(key))
+            .is_some();
+        if !exists && !ENABLING.contains(&path) {
+            return Err(format!(
+                "{} can not be set while {} is off",
+                path,
+                parents.join(".")
+            ));
+        }
+        let table = table_at(value, parents);
+        let typed = match table.get(*last) {
+            Some(Value::String(_)) => Value::String(setting.to_string()),
+            Some(Value::Integer(_)) => setting
+                .parse()
+                .map(Value::Integer)
+                .unwrap_or_else(|_| Value::String(setting.to_string())),
+            Some(Value::Float(_)) => setting
+                .parse()
+                .map(Value::Float)
+                .unwrap_or_else(|_| Value::String(setting.to_string())),
+            Some(Value::Boolean(_)) => setting
+                .parse()
+                .map(Value::Boolean)
+                .unwrap_or_else(|_| Value::String(setting.to_string())),
+            _ => infer(setting),
+        };
+        table.insert(last.to_string(), typed);
+    }
+    Ok(())
+}
+
+fn positive(number: f64) -> bool {
+    number.is_finite() && number > 0f64
+}
+
+fn non_negative(number: f64) -> bool {
+    number.is_finite() && number >= 0f64
+}
+
+fn probability(number: f64) -> bool {
+    number > 0f64 && number <= 1f64
+}
+
+fn infer(setting: &str) -> Value {
+    if let Ok(boolean) = setting.parse() {
+        Value::Boolean(boolean)
+    } else if let Ok(integer) = setting.parse() {
+        Value::Integer(integer)
+    } else if let Ok(float) = setting.parse() {
+        Value::Float(float)
+    } else {
+        Value::String(setting.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn environment_and_overrides_should_overlay_the_defaults() {
+        let environment: HashMap<&str, &str> = vec![
+            ("heartbeat_sleep_duration", "5"),
+            ("food_spawn_rate", "0.2"),
+            ("energy", "true"),
+            ("tick", "50"),
+        ]
+        .into_iter()
+        .collect();
+        let overrides = vec![("clock.tick".to_string(), "20".to_string())];
+
+        let config = Config::load(
+            None,
+            |key| environment.get(key).map(|value| value.to_string()),
+            &overrides,
+        )
+        .unwrap();
+
+        assert_eq!(config.heartbeat.sleep_duration, 5);
+        assert_eq!(config.clock.tick, 20);
+        assert_eq!(config.arena.foraging.unwrap().spawn_rate, 0.2);
+        assert_eq!(config.arena.energy, Some(EnergyRule::default()));
+        assert_eq!(config.arena.capture_the_flag, None);
+    }
+
+    #[test]
+    fn invalid_settings_should_be_reported() {
+        let overrides = vec![
+            ("clock.tick".to_string(), "0".to_string()),
+            ("arena.wind".to_string(), "breeze".to_string()),
+        ];
+
+        let errors = Config::load(None, |_| None, &overrides).unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("clock.tick"));
+        assert!(errors[1].contains("arena.wind"));
+
+        let slow = vec![("clock.tick".to_string(), "3600000".to_string())];
+        assert!(Config::load(None, |_| None, &slow).is_err());
+
+        let typo = vec![("clock.tikc".to_string(), "20".to_string())];
+        let errors = Config::load(None, |_| None, &typo).unwrap_err();
+        assert!(errors[0].contains("tikc"));
+    }
+
+    #[test]
+    fn settings_of_rules_that_are_off_should_be_reported() {
+        let environment: HashMap<&str, &str> = vec![("food_patch_size", "3"), ("max_age", "100")]
+            .into_iter()
+            .collect();
+
+        let errors = Config::load(
+            None,
+            |key| environment.get(key).map(|value| value.to_string()),
+            &[],
+        )
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("arena.foraging is off"));
+        assert!(errors[1].contains("arena.energy is off"));
+
+        let overrides = vec![
+            ("arena.foraging.patch_size".to_string(), "3".to_string()),
+            ("arena.foraging.spawn_rate".to_string(), "0.1".to_string()),
+        ];
+        let config = Config::load(None, |_| None, &overrides).unwrap();
+        assert_eq!(config.arena.foraging.unwrap().patch_size, 3f64);
+    }
+
+    #[test]
+    fn arena_settings_out_of_range_should_be_reported() {
+        let overrides: Vec<(String, String)> = vec![
+            ("arena.collisions.radius", "-0.01"),
+            ("arena.reproduction.rate", "1.5"),
+            ("arena.race.checkpoints", "0.2,0.2"),
+            ("arena.race.finishers", "0"),
+            ("arena.foraging.spawn_rate", "0"),
+        ]
+        .into_iter()
+        .map(|(path, setting)| (path.to_string(), setting.to_string()))
+        .collect();
+
+        let errors = Config::load(None, |_| None, &overrides).unwrap_err();
+
+        assert_eq!(errors.len(), 4);
+        assert!(errors[0].contains("arena.collisions.radius"));
+        assert!(errors[1].contains("arena.foraging.spawn_rate"));
+        assert!(errors[2].contains("arena.reproduction.rate"));
+        assert!(errors[3].contains("arena.race.finishers"));
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    io::{self, copy, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const REQUEST_HEAD_LENGTH: usize = 8192;
+const REQUEST_HEAD_TIMEOUT: Duration = Duration::from_secs(2);
+pub const MAX_CONNECTIONS: usize = 128;
+
+/// Serves HTTP, and websocket when it is given, on a single address by
+/// forwarding every connection to either the HTTP server or the websocket
+/// server, depending on its path. Stopping the gateway releases the address.
+///
+/// HTTP connections are closed after the first response, so every request is
+/// routed by its own path. The address of the client is passed on in the
+/// `X-Forwarded-For` header.
+pub struct Gateway {
+    listener: TcpListener,
+    http_address: SocketAddr,
+    websocket_address: Option<String>,
+    websocket_path: String,
+    max_connections: usize,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Makes a serving gateway return and close its listener.
+pub struct Stopper {
+    stopped: Arc<AtomicBool>,
+    address: SocketAddr,
+}
+
+impl Stopper {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // The gateway only sees the flag once `accept` returns.
+        if let Err(error) = TcpStream::connect(self.address) {
+            error!("could not wake up the gateway: {}", error);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Route {
+    Http,
+    Websocket,
+}
+
+/// Counts a forwarded connection until it is closed.
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Gateway {
+    pub fn new(listener: TcpListener, http_address: SocketAddr) -> Self {
+        Self {
+            listener,
+            http_address,
+            websocket_address: None,
+            websocket_path: "/ws".to_string(),
+            max_connections: MAX_CONNECTIONS,
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Passes on the connections to `websocket_path` to this address.
+    pub fn websocket<T>(mut self, websocket_address: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.websocket_address = Some(websocket_address.into());
+        self
+    }
+
+    pub fn websocket_path<S>(mut self, websocket_path: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.websocket_path = websocket_path.into();
+        self
+    }
+
+    /// How many connections are forwarded at the same time. Others are refused.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn stopper(&self) -> io::Result<Stopper> {
+        let mut address = self.listener.local_addr()?;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        Ok(Stopper {
+            stopped: Arc::clone(&self.stopped),
+            address,
+        })
+    }
+
+    /// Forwards connections until it is stopped.
+    pub fn serve(&self) {
+        let connections = Arc::new(AtomicUsize::new(0));
+        for stream in self.listener.incoming() {
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            match stream {
+                Ok(stream) => {
+                    if connections.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                        warn!(
+                            "refusing connection from {}, too many connections",
+                            peer(&stream)
+                        );
+                        continue;
+                    }
+                    let connection = Connection(Arc::clone(&connections));
+                    let (http_address, websocket_address, websocket_path) = (
+                        self.http_address.to_string(),
+                        self.websocket_address.clone(),
+                        self.websocket_path.clone(),
+                    );
+                    let spawned =
+                        thread::Builder::new()
+                            .name("gateway".to_string())
+                            .spawn(move || {
+                                let _connection = connection;
+                                let passed = pass(
+                                    stream,
+                                    &http_address,
+                                    websocket_address.as_deref(),
+                                    &websocket_path,
+                                );
+                                if let Err(error) = passed {
+                                    error!("could not forward connection: {}", error);
+                                }
+                            });
+                    if let Err(error) = spawned {
+                        error!("could not start forwarding: {}", error);
+                    }
+                }
+                Err(error) => error!("could not accept connection: {}", error),
+            }
+        }
+    }
+}
+
+fn peer(stream: &TcpStream) -> String {
+    stream
+        .peer_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_else(|_| "unknown peer".to_string())
+}
+
+fn pass(
+    mut stream: TcpStream,
+    http_address: &str,
+    websocket_address: Option<&str>,
+    websocket_path: &str,
+) -> io::Result<()> {
+    let (head, rest) = request_head(&mut stream)?;
+    let route = match websocket_address {
+        Some(_) => route(head.lines().next().unwrap_or(""), websocket_path),
+        None => Route::Http,
+    };
+    let client = stream.peer_addr()?;
+    info!(
+        "forwarding {} from {} to {:?}",
+        head.lines().next().unwrap_or(""),
+        client,
+        route
+    );
+    let mut forwarded = rewrite(&head, &route, client).into_bytes();
+    forwarded.extend_from_slice(&rest);
+    match (route, websocket_address) {
+        (Route::Websocket, Some(websocket_address)) => {
+            forward(stream, websocket_address, &forwarded)
+        }
+        _ => forward(stream, http_address, &forwarded),
+    }
+}
+
+/// Reads the request head up to the empty line that ends it. Returns the head
+/// and whatever was read after it.
+fn request_head(stream: &mut TcpStream) -> io::Result<(String, Vec<u8>)> {
+    let deadline = Instant::now() + REQUEST_HEAD_TIMEOUT;
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let end = loop {
+        if let Some(end) = head_end(&buffer) {
+            break end;
+        }
+        if buffer.len() >= REQUEST_HEAD_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too long",
+            ));
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_secs(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request head not received in time",
+            ));
+        }
+        stream.set_read_timeout(Some(remaining))?;
+        let read = stream.read(&mut chunk)?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before the request head",
+            ));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+    stream.set_read_timeout(None)?;
+    let rest = buffer.split_off(end);
+    Ok((String::from_utf8_lossy(&buffer).into_owned(), rest))
+}
+
+fn head_end(buffer: &[u8]) -> Option<usize> {
+    (0..buffer.len()).find_map(|start| {
+        if buffer[start..].starts_with(b"\r\n\r\n") {
+            Some(start + 4)
+        } else if buffer[start..].starts_with(b"\n\n") {
+            Some(start + 2)
+        } else {
+            None
+        }
+    })
+}
+
+fn route(request_line: &str, websocket_path: &str) -> Route {
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|target| target.split('?').next())
+        .unwrap_or("");
+    if path == websocket_path {
+        Route::Websocket
+    } else {
+        Route::Http
+    }
+}
+
+/// Passes on the address of the client and asks the HTTP server to close the
+/// connection after its response.
+fn rewrite(head: &str, route: &Route, client: SocketAddr) -> String {
+    let mut lines = head.lines().filter(|line| !line.is_empty());
+    let mut rewritten: Vec<String> = lines.next().into_iter().map(str::to_string).collect();
+    for header in lines {
+        let name = header.split(':').next().unwrap_or("").trim();
+        let replaced = name.eq_ignore_ascii_case("x-forwarded-for")
+            || (*route == Route::Http && name.eq_ignore_ascii_case("connection"));
+        if !replaced {
+            rewritten.push(header.to_string());
+        }
+    }
+    rewritten.push(format!("X-Forwarded-For: {}", client.ip()));
+    if *route == Route::Http {
+        rewritten.push("Connection: close".to_string());
+    }
+    rewritten.join("\r\n") + "\r\n\r\n"
+}
+
+fn forward(client: TcpStream, target: &str, head: &[u8]) -> io::Result<()> {
+    let mut upstream = TcpStream::connect(target)?;
+    upstream.write_all(head)?;
+    let (mut client_reader, mut upstream_writer) = (client.try_clone()?, upstream.try_clone()?);
+    let requests = thread::Builder::new()
+        .name("gateway".to_string())
+        .spawn(move || {
+            let _ = copy(&mut client_reader, &mut upstream_writer);
+            let _ = upstream_writer.shutdown(Shutdown::Write);
+        })?;
+    let (mut upstream_reader, mut client_writer) = (upstream, client);
+    let result = copy(&mut upstream_reader, &mut client_writer);
+    let _ = client_writer.shutdown(Shutdown::Both);
+    let _ = requests.join();
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_should_be_routed_by_path() {
+        assert_eq!(
+            route("GET /ws?encoding=msgpack HTTP/1.1", "/ws"),
+            Route::Websocket
+        );
+        assert_eq!(route("GET /ws HTTP/1.1", "/ws"), Route::Websocket);
+        assert_eq!(route("GET /wsx HTTP/1.1", "/ws"), Route::Http);
+        assert_eq!(route("GET / HTTP/1.1", "/ws"), Route::Http);
+        assert_eq!(route("POST /register HTTP/1.1", "/ws"), Route::Http);
+        assert_eq!(route("", "/ws"), Route::Http);
+    }
+
+    #[test]
+    fn http_requests_should_close_the_connection_and_name_the_client() {
+        let client: SocketAddr = "192.168.1.7:52000".parse().unwrap();
+        let head = "GET /scores HTTP/1.1\r\nHost: bws\r\nConnection: keep-alive\r\n\r\n";
+
+        assert_eq!(
+            rewrite(head, &Route::Http, client),
+            "GET /scores HTTP/1.1\r\nHost: bws\r\nX-Forwarded-For: 192.168.1.7\r\nConnection: close\r\n\r\n"
+        );
+        assert_eq!(
+            rewrite(
+                "GET /ws HTTP/1.1\nConnection: Upgrade\n\n",
+                &Route::Websocket,
+                client
+            ),
+            "GET /ws HTTP/1.1\r\nConnection: Upgrade\r\nX-Forwarded-For: 192.168.1.7\r\n\r\n"
+        );
+        assert_eq!(head_end(b"GET / HTTP/1.1\r\n\r\nbody"), Some(18));
+        assert_eq!(head_end(b"GET / HTTP/1.1\r\n"), None);
+    }
+}
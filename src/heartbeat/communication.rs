@@ -2,4 +2,5 @@ use hyper::Uri;
 
 pub enum Message {
     Check(Vec<(String, Uri)>),
+    Stop,
 }
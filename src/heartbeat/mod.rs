@@ -1,8 +1,7 @@
 pub mod communication;
 
 use std::{
-    sync::mpsc::{Receiver, Sender},
-    thread,
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
     time::Duration,
 };
 
@@ -37,7 +36,10 @@ impl Heartbeat {
         let client = Client::new(&core.handle());
 
         loop {
-            thread::sleep(self.sleep_duration);
+            match self.rx.recv_timeout(self.sleep_duration) {
+                Ok(HeartbeatMessage::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                Ok(HeartbeatMessage::Check(_)) | Err(RecvTimeoutError::Timeout) => (),
+            }
             if let Err(error) = self.tx.send(SimulationMessage::Heartbeat) {
                 error!("could not send heartbeat: {:?}", error);
             } else if let Ok(message) = self.rx.recv() {
@@ -70,9 +72,11 @@ impl Heartbeat {
                             }
                         }
                     }
+                    HeartbeatMessage::Stop => return,
                 }
             } else {
-                error!("could not receive message")
+                error!("could not receive message");
+                return;
             }
         }
     }
@@ -0,0 +1,506 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpListener},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use iron::Iron;
+use serde_json::Value;
+
+use crate::{
+    brain::{communication::Message as BrainMessage, Brain},
+    clock::{communication::Message as ClockMessage, Clock},
+    config::Config,
+    gateway::Gateway,
+    heartbeat::{communication::Message as HeartbeatMessage, Heartbeat},
+    persistence::{self, communication::Message as PersistenceMessage, Persistence},
+    server::{self, Websocket},
+    simulation::{
+        communication::Message as SimulationMessage,
+        leaderboard::{Leaderboard, Standing},
+        score::Scores,
+        token, Registration, Simulation,
+    },
+    supervisor::{Supervisor, Trigger},
+    websocket::{communication::Message as WsMessage, WebSocketUpdate},
+};
+
+/// Starts a bws instance from a typed configuration.
+pub struct ServerBuilder {
+    config: Config,
+    restore: bool,
+    paused: bool,
+    http: bool,
+    websocket: bool,
+    signals: bool,
+}
+
+impl ServerBuilder {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            restore: false,
+            paused: false,
+            http: true,
+            websocket: true,
+            signals: false,
+        }
+    }
+
+    /// Restores the teams from the snapshot.
+    pub fn restore(mut self, restore: bool) -> Self {
+        self.restore = restore;
+        self
+    }
+
+    /// Starts with the clock paused, so the simulation only advances by `Instance::step`.
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    pub fn http(mut self, http: bool) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn websocket(mut self, websocket: bool) -> Self {
+        self.websocket = websocket;
+        self
+    }
+
+    /// Shuts the instance down on SIGINT and SIGTERM.
+    pub fn handle_signals(mut self, signals: bool) -> Self {
+        self.signals = signals;
+        self
+    }
+
+    pub fn start(self) -> Result<Instance, String> {
+        let config = self.config;
+        config.validate().map_err(|errors| errors.join(", "))?;
+        let arena = config.arena.build()?;
+        let reply_timeout = config.timeouts.reply();
+        let single_port = config.server.single_port && self.http && self.websocket;
+        let admin_token = config.server.admin_token.clone().unwrap_or_else(|| {
+            let admin_token = token::generate();
+            info!("generated admin token {}", admin_token);
+            admin_token
+        });
+
+        let (simulation_tx, simulation_rx): (
+            Sender<SimulationMessage>,
+            Receiver<SimulationMessage>,
+        ) = channel();
+        let (heartbeat_tx, heartbeat_rx): (Sender<HeartbeatMessage>, Receiver<HeartbeatMessage>) =
+            channel();
+        let (brain_tx, brain_rx): (Sender<BrainMessage>, Receiver<BrainMessage>) = channel();
+        let (ws_tx, ws_rx): (Sender<WsMessage>, Receiver<WsMessage>) = channel();
+        let (clock_tx, clock_rx): (Sender<ClockMessage>, Receiver<ClockMessage>) = channel();
+        let (persistence_tx, persistence_rx): (
+            Sender<PersistenceMessage>,
+            Receiver<PersistenceMessage>,
+        ) = channel();
+
+        let gateway = if self.http {
+            let listener = TcpListener::bind(&config.server.address).map_err(|error| {
+                format!("could not listen on {}: {}", config.server.address, error)
+            })?;
+            let page_config = server::Config {
+                websocket: match config.socket_port() {
+                    Some(port) if !single_port => Websocket::Port(port),
+                    _ => Websocket::Path(config.server.websocket_path.clone()),
+                },
+            };
+            info!("starting server");
+            let mut listening =
+                Iron::new(server::chain(&simulation_tx, page_config, reply_timeout))
+                    .http("127.0.0.1:0")
+                    .map_err(|error| format!("could not start server: {}", error))?;
+            info!("server bound to address {}", listening.socket);
+            // hyper can not stop its server and joins it when dropped, so it is
+            // detached on an ephemeral loopback port, behind the gateway.
+            if let Err(error) = listening.close() {
+                error!("could not detach server: {}", error);
+            }
+            let gateway = Gateway::new(listener, listening.socket);
+            Some(if single_port {
+                gateway
+                    .websocket(config.server.socket.clone())
+                    .websocket_path(config.server.websocket_path.clone())
+            } else {
+                gateway
+            })
+        } else {
+            None
+        };
+        let http_address = gateway
+            .as_ref()
+            .map(Gateway::local_addr)
+            .transpose()
+            .map_err(|error| format!("could not find the gateway address: {}", error))?;
+
+        let mut supervisor = Supervisor::new().grace(config.timeouts.shutdown());
+        if self.signals {
+            supervisor
+                .handle_signals()
+                .map_err(|error| format!("could not handle signals: {}", error))?;
+        }
+        let spawned = |error| format!("could not start subsystem: {}", error);
+
+        let snapshot_path = config.snapshot.path.clone();
+        let stop_persistence_tx = persistence_tx.clone();
+        supervisor
+            .spawn(
+                "persistence",
+                move || {
+                    info!("starting persistence to {}", snapshot_path);
+                    let mut persistence = Persistence::new(snapshot_path, persistence_rx);
+                    persistence.store();
+                },
+                move || stop(&stop_persistence_tx, PersistenceMessage::Stop),
+            )
+            .map_err(spawned)?;
+
+        let (restore, snapshot_path) = (self.restore, config.snapshot.path.clone());
+        let persist_flocks = config.snapshot.flocks;
+        let population_cap = config.simulation.population_cap;
+        let leaderboard = Leaderboard::new(config.simulation.leaderboard_interval)
+            .history_length(config.simulation.leaderboard_history);
+        let simulation_brain_tx = brain_tx.clone();
+        let simulation_heartbeat_tx = heartbeat_tx.clone();
+        let simulation_ws_tx = ws_tx.clone();
+        let stop_simulation_tx = simulation_tx.clone();
+        supervisor
+            .spawn(
+                "simulation",
+                move || {
+                    info!("starting simulation");
+
+                    let simulation = if restore {
+                        match persistence::restore(&snapshot_path) {
+                            Ok(snapshot) => {
+                                info!("restored teams from {}", snapshot_path);
+                                Simulation::restore(snapshot)
+                            }
+                            Err(error) => {
+                                error!("{}", error);
+                                Simulation::new()
+                            }
+                        }
+                    } else {
+                        Simulation::new()
+                    };
+                    let mut simulation = simulation
+                        .arena(arena)
+                        .persist_flocks(persist_flocks)
+                        .leaderboard(leaderboard)
+                        .population_cap(population_cap);
+                    simulation.start(
+                        simulation_rx,
+                        simulation_brain_tx,
+                        simulation_heartbeat_tx,
+                        simulation_ws_tx,
+                        persistence_tx,
+                    );
+                },
+                move || stop(&stop_simulation_tx, SimulationMessage::Stop),
+            )
+            .map_err(spawned)?;
+
+        if let (Some(gateway), Some(address)) = (gateway, http_address) {
+            let stopper = gateway
+                .stopper()
+                .map_err(|error| format!("could not start gateway: {}", error))?;
+            let websocket_path = config.server.websocket_path.clone();
+            supervisor
+                .spawn(
+                    "gateway",
+                    move || {
+                        if single_port {
+                            info!(
+                                "serving http and websocket on {} at {}",
+                                address, websocket_path
+                            );
+                        } else {
+                            info!("serving http on {}", address);
+                        }
+                        gateway.serve();
+                    },
+                    move || stopper.stop(),
+                )
+                .map_err(spawned)?;
+        }
+
+        let sleep_duration = Duration::from_secs(config.heartbeat.sleep_duration);
+        let heartbeat_simulation_tx = simulation_tx.clone();
+        supervisor
+            .spawn(
+                "heartbeat",
+                move || {
+                    info!("starting heartbeat");
+                    let mut heartbeat =
+                        Heartbeat::new(sleep_duration, heartbeat_rx, heartbeat_simulation_tx);
+                    heartbeat.monitor();
+                },
+                move || stop(&heartbeat_tx, HeartbeatMessage::Stop),
+            )
+            .map_err(spawned)?;
+
+        let brain_simulation_tx = simulation_tx.clone();
+        supervisor
+            .spawn(
+                "brain",
+                move || {
+                    info!("starting brain");
+                    let mut brain = Brain::new(brain_rx, brain_simulation_tx);
+                    brain.think();
+                },
+                move || stop(&brain_tx, BrainMessage::Stop),
+            )
+            .map_err(spawned)?;
+
+        if self.websocket {
+            let ws_update = WebSocketUpdate::new(config.server.socket.clone())
+                .admin_token(admin_token.clone())
+                .keyframe_interval(config.websocket.keyframe_interval)
+                .max_pending(config.websocket.max_pending)
+                .max_dropped(config.websocket.max_dropped)
+                .reply_timeout(reply_timeout);
+            let (ws_simulation_tx, ws_clock_tx) = (simulation_tx.clone(), clock_tx.clone());
+            supervisor
+                .spawn(
+                    "socket",
+                    move || {
+                        info!("starting websocket communication");
+                        ws_update.dispatch(ws_simulation_tx, ws_clock_tx, ws_rx);
+                    },
+                    move || stop(&ws_tx, WsMessage::Stop),
+                )
+                .map_err(spawned)?;
+        } else {
+            supervisor
+                .spawn(
+                    "updates",
+                    move || {
+                        for message in ws_rx {
+                            if let WsMessage::Stop = message {
+                                break;
+                            }
+                        }
+                    },
+                    move || stop(&ws_tx, WsMessage::Stop),
+                )
+                .map_err(spawned)?;
+        }
+
+        let tick_duration = Duration::from_millis(config.clock.tick);
+        let (paused, clock_simulation_tx, stop_clock_tx) =
+            (self.paused, simulation_tx.clone(), clock_tx.clone());
+        supervisor
+            .spawn(
+                "clock",
+                move || {
+                    info!("starting clock");
+                    let mut clock =
+                        Clock::new(tick_duration, clock_rx, clock_simulation_tx).paused(paused);
+                    clock.start();
+                },
+                move || stop(&stop_clock_tx, ClockMessage::Stop),
+            )
+            .map_err(spawned)?;
+
+        let trigger = supervisor.trigger();
+        let supervisor = thread::Builder::new()
+            .name("supervisor".to_string())
+            .spawn(move || supervisor.supervise())
+            .map_err(spawned)?;
+
+        Ok(Instance {
+            simulation_tx,
+            clock_tx,
+            trigger,
+            supervisor,
+            http_address,
+            admin_token,
+            reply_timeout,
+        })
+    }
+}
+
+/// A running bws instance.
+pub struct Instance {
+    simulation_tx: Sender<SimulationMessage>,
+    clock_tx: Sender<ClockMessage>,
+    trigger: Trigger,
+    supervisor: JoinHandle<Result<(), String>>,
+    http_address: Option<SocketAddr>,
+    admin_token: String,
+    reply_timeout: Duration,
+}
+
+impl Instance {
+    /// The address HTTP clients connect to, when the HTTP server is started.
+    /// This is the address of the gateway, which is released by `shutdown`.
+    pub fn http_address(&self) -> Option<SocketAddr> {
+        self.http_address
+    }
+
+    pub fn admin_token(&self) -> &str {
+        &self.admin_token
+    }
+
+    /// Sends a message to the simulation.
+    pub fn send(&self, message: SimulationMessage) -> Result<(), String> {
+        self.simulation_tx
+            .send(message)
+            .map_err(|_| "simulation stopped".to_string())
+    }
+
+    /// Sends a request to the simulation and waits for its reply.
+    pub fn ask<T, F>(&self, message: F) -> Result<T, String>
+    where
+        F: FnOnce(Sender<T>) -> SimulationMessage,
+    {
+        let (reply_tx, reply_rx) = channel();
+        self.send(message(reply_tx))?;
+        reply_rx
+            .recv_timeout(self.reply_timeout)
+            .map_err(|error| format!("no reply from simulation: {}", error))
+    }
+
+    /// Advances the simulation by one tick and returns the resulting state.
+    pub fn step(&self) -> Result<Value, String> {
+        self.send(SimulationMessage::Tick)?;
+        self.state()
+    }
+
+    /// The teams and the world, as sent to websocket clients, and the current tick.
+    pub fn state(&self) -> Result<Value, String> {
+        self.ask(SimulationMessage::State)
+    }
+
+    pub fn register(&self, registration: Registration) -> Result<String, String> {
+        self.ask(|reply_tx| SimulationMessage::Register(registration, reply_tx))?
+    }
+
+    pub fn scores(&self) -> Result<HashMap<String, Scores>, String> {
+        self.ask(SimulationMessage::Scores)
+    }
+
+    pub fn standings(&self) -> Result<Vec<Standing>, String> {
+        self.ask(SimulationMessage::Standings)
+    }
+
+    /// The standings and the history of every team.
+    pub fn leaderboard(&self) -> Result<Leaderboard, String> {
+        self.ask(SimulationMessage::Leaderboard)
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.clock(ClockMessage::Pause)
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.clock(ClockMessage::Resume)
+    }
+
+    fn clock(&self, message: ClockMessage) -> Result<(), String> {
+        self.clock_tx
+            .send(message)
+            .map_err(|_| "clock stopped".to_string())
+    }
+
+    /// Waits until the instance stops, by a signal or because a subsystem stopped.
+    pub fn wait(self) -> Result<(), String> {
+        self.supervisor
+            .join()
+            .unwrap_or_else(|_| Err("supervisor panicked".to_string()))
+    }
+
+    pub fn shutdown(self) -> Result<(), String> {
+        self.trigger.shutdown();
+        self.wait()
+    }
+}
+
+fn stop<M>(tx: &Sender<M>, message: M) {
+    if tx.send(message).is_err() {
+        error!("could not send stop message");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::{env, fs, process};
+
+    #[test]
+    fn an_instance_should_step_and_shut_down() {
+        let mut config = Config::default();
+        config.snapshot.path = env::temp_dir()
+            .join(format!("bws-instance-{}.json", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let instance = ServerBuilder::new(config.clone())
+            .paused(true)
+            .http(false)
+            .websocket(false)
+            .start()
+            .unwrap();
+
+        let registration =
+            serde_json::from_value(json!({"name": "blue", "ip_address": "127.0.0.1", "port": 9}))
+                .unwrap();
+        assert!(instance.register(registration).is_ok());
+        instance.step().unwrap();
+        let state = instance.step().unwrap();
+
+        assert_eq!(state["tick"], 2);
+        assert!(state["teams"]["blue"].is_object());
+        assert_eq!(instance.shutdown(), Ok(()));
+        let _ = fs::remove_file(config.snapshot.path);
+    }
+
+    #[test]
+    fn a_stopped_instance_should_release_its_address() {
+        let mut config = Config::default();
+        config.server.address = "127.0.0.1:0".to_string();
+        config.server.socket = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .to_string();
+        config.snapshot.path = env::temp_dir()
+            .join(format!("bws-gateway-{}.json", process::id()))
+            .to_string_lossy()
+            .into_owned();
+
+        let instance = ServerBuilder::new(config.clone())
+            .paused(true)
+            .start()
+            .unwrap();
+        let address = instance.http_address().unwrap();
+        assert_ne!(address.port(), 0);
+        assert_eq!(instance.shutdown(), Ok(()));
+
+        config.server.address = address.to_string();
+        let instance = ServerBuilder::new(config.clone())
+            .paused(true)
+            .start()
+            .unwrap();
+        assert_eq!(instance.http_address(), Some(address));
+        assert_eq!(instance.shutdown(), Ok(()));
+
+        config.server.single_port = false;
+        let instance = ServerBuilder::new(config.clone())
+            .paused(true)
+            .start()
+            .unwrap();
+        assert_eq!(instance.http_address(), Some(address));
+        assert_eq!(instance.shutdown(), Ok(()));
+        assert!(TcpListener::bind(address).is_ok());
+        let _ = fs::remove_file(config.snapshot.path);
+    }
+}
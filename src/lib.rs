@@ -6,18 +6,28 @@ extern crate log;
 extern crate logger;
 extern crate mount;
 extern crate random;
+extern crate rmp_serde;
 extern crate router;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate signal_hook;
 extern crate staticfile;
 extern crate tokio_core;
+extern crate toml;
 extern crate ws;
 
 pub mod brain;
 pub mod clock;
+pub mod config;
+pub mod gateway;
 pub mod heartbeat;
+pub mod instance;
+pub mod persistence;
 pub mod server;
 pub mod simulation;
+pub mod supervisor;
 pub mod websocket;
+
+pub use instance::{Instance, ServerBuilder};
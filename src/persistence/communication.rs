@@ -0,0 +1,4 @@
+pub enum Message {
+    Snapshot(String),
+    Stop,
+}
@@ -0,0 +1,88 @@
+pub mod communication;
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, io, iter,
+    path::PathBuf,
+    sync::mpsc::Receiver,
+};
+
+use self::communication::Message as PersistenceMessage;
+use crate::simulation::Snapshot;
+
+pub struct Persistence {
+    path: PathBuf,
+    rx: Receiver<PersistenceMessage>,
+}
+
+impl Persistence {
+    pub fn new<P>(path: P, rx: Receiver<PersistenceMessage>) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            rx,
+        }
+    }
+
+    pub fn store(&mut self) {
+        loop {
+            match self.rx.recv() {
+                Ok(message) => {
+                    let (mut latest, mut stopping) = (None, false);
+                    for message in iter::once(message).chain(self.rx.try_iter()) {
+                        match message {
+                            PersistenceMessage::Snapshot(json) => latest = Some(json),
+                            PersistenceMessage::Stop => stopping = true,
+                        }
+                    }
+                    if let Some(json) = latest {
+                        if let Err(error) = self.write(&json) {
+                            error!("could not write snapshot to {:?}: {}", self.path, error);
+                        }
+                    }
+                    if stopping {
+                        break;
+                    }
+                }
+
+                Err(error) => {
+                    error!("could not receive message: {}", error);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn write(&self, json: &str) -> io::Result<()> {
+        let intermediate = self.path.with_extension("tmp");
+        fs::write(&intermediate, json)?;
+        fs::rename(&intermediate, &self.path)
+    }
+}
+
+pub fn restore<P>(path: P) -> Result<Snapshot, Error>
+where
+    P: Into<PathBuf>,
+{
+    let json = fs::read_to_string(path.into()).map_err(Error::CouldNotRead)?;
+    serde_json::from_str(&json).map_err(Error::CouldNotDeserialize)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    CouldNotRead(io::Error),
+    CouldNotDeserialize(serde_json::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::CouldNotRead(error) => write!(f, "could not read snapshot: {}", error),
+            Error::CouldNotDeserialize(error) => {
+                write!(f, "could not deserialize snapshot: {}", error)
+            }
+        }
+    }
+}
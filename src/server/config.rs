@@ -0,0 +1,27 @@
+use iron::{status, Request, Response};
+use router::Router;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Config {
+    pub websocket: Websocket,
+}
+
+/// Where the page finds the websocket, relative to the address it was served from.
+#[derive(Serialize, Clone, Debug)]
+pub enum Websocket {
+    Path(String),
+    Port(u16),
+}
+
+pub fn router(config: Config) -> Router {
+    let mut router = Router::new();
+
+    let payload = serde_json::to_string(&config).unwrap();
+    router.get(
+        "/",
+        move |_: &mut Request| Ok(Response::with((status::Ok, payload.clone()))),
+        "config",
+    );
+
+    router
+}
@@ -0,0 +1,70 @@
+use std::{
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::Duration,
+};
+
+use iron::{headers::ContentType, status, Request, Response};
+use router::Router;
+
+use super::ask;
+use crate::simulation::communication::Message;
+
+pub fn router(tx: &Sender<Message>, timeout: Duration) -> Router {
+    let mut router = Router::new();
+
+    let standings_tx = tx.clone();
+    let standings_tx_mutex = Arc::new(Mutex::new(standings_tx));
+    router.get(
+        "/",
+        move |_: &mut Request| {
+            if let Some(standings) = ask(&standings_tx_mutex, timeout, Message::Standings) {
+                let payload = serde_json::to_string(&standings).unwrap();
+
+                Ok(Response::with((status::Ok, payload)))
+            } else {
+                Ok(Response::with(status::ServiceUnavailable))
+            }
+        },
+        "leaderboard",
+    );
+
+    let export_tx = tx.clone();
+    let export_tx_mutex = Arc::new(Mutex::new(export_tx));
+    router.get(
+        "/export/:format",
+        move |request: &mut Request| {
+            let format = request
+                .extensions
+                .get::<Router>()
+                .and_then(|parameters| parameters.find("format"))
+                .unwrap_or("json")
+                .to_string();
+            if let Some(leaderboard) = ask(&export_tx_mutex, timeout, Message::Leaderboard) {
+                match format.as_ref() {
+                    "json" => {
+                        let payload = serde_json::to_string(&leaderboard).unwrap();
+
+                        Ok(Response::with((status::Ok, payload)))
+                    }
+                    "csv" => {
+                        let mut response = Response::with((status::Ok, leaderboard.to_csv()));
+                        response
+                            .headers
+                            .set(ContentType("text/csv".parse().unwrap()));
+
+                        Ok(response)
+                    }
+                    _ => Ok(Response::with((
+                        status::NotFound,
+                        format!("unknown export format \"{}\"", format),
+                    ))),
+                }
+            } else {
+                Ok(Response::with(status::ServiceUnavailable))
+            }
+        },
+        "export",
+    );
+
+    router
+}
@@ -1,16 +1,28 @@
+mod config;
+mod leaderboard;
 mod register;
-
-use std::{path::Path, sync::mpsc::Sender};
+mod score;
+mod team;
+
+use std::{
+    path::Path,
+    sync::{
+        mpsc::{channel, Sender},
+        Mutex,
+    },
+    time::Duration,
+};
 
 use iron::Chain;
 use logger::Logger;
 use mount::Mount;
 use staticfile::Static;
 
+pub use self::config::{Config, Websocket};
 use crate::simulation::communication::Message;
 
-pub fn chain(tx: &Sender<Message>) -> Chain {
-    let mut chain = Chain::new(mount(tx));
+pub fn chain(tx: &Sender<Message>, config: Config, timeout: Duration) -> Chain {
+    let mut chain = Chain::new(mount(tx, config, timeout));
     let (logger_before, logger_after) = Logger::new(None);
     chain.link_before(logger_before);
     chain.link_after(logger_after);
@@ -18,11 +30,34 @@ pub fn chain(tx: &Sender<Message>) -> Chain {
     chain
 }
 
-fn mount(tx: &Sender<Message>) -> Mount {
+fn mount(tx: &Sender<Message>, config: Config, timeout: Duration) -> Mount {
     let mut mount = Mount::new();
 
     mount.mount("/", Static::new(Path::new("static/")));
-    mount.mount("/register", register::router(tx));
+    mount.mount("/register", register::router(tx, timeout));
+    mount.mount("/scores", score::router(tx, timeout));
+    mount.mount("/leaderboard", leaderboard::router(tx, timeout));
+    mount.mount("/teams", team::router(tx, timeout));
+    mount.mount("/config", config::router(config));
 
     mount
 }
+
+fn ask<T, F>(tx: &Mutex<Sender<Message>>, timeout: Duration, message: F) -> Option<T>
+where
+    F: FnOnce(Sender<T>) -> Message,
+{
+    let (reply_tx, reply_rx) = channel();
+    if tx.lock().unwrap().send(message(reply_tx)).is_err() {
+        error!("could not send request to simulation");
+        return None;
+    }
+
+    match reply_rx.recv_timeout(timeout) {
+        Ok(reply) => Some(reply),
+        Err(error) => {
+            error!("did not receive reply from simulation: {}", error);
+            None
+        }
+    }
+}
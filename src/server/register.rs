@@ -1,15 +1,17 @@
 use std::{
     io::Read,
     sync::{mpsc::Sender, Arc, Mutex},
+    time::Duration,
 };
 
 use iron::{status, Request, Response};
 use router::Router;
 use serde_json::{self, Error};
 
+use super::ask;
 use crate::simulation::{communication::Message, *};
 
-pub fn router(tx: &Sender<Message>) -> Router {
+pub fn router(tx: &Sender<Message>, timeout: Duration) -> Router {
     let mut router = Router::new();
 
     let registration_tx = tx.clone();
@@ -23,13 +25,23 @@ pub fn router(tx: &Sender<Message>) -> Router {
                 if let Ok(registration) = registration_result {
                     info!("received {:?}", registration);
 
-                    registration_tx_mutex
-                        .lock()
-                        .unwrap()
-                        .send(Message::Register(registration))
-                        .unwrap();
+                    match ask(&registration_tx_mutex, timeout, |reply_tx| {
+                        Message::Register(registration, reply_tx)
+                    }) {
+                        Some(Ok(token)) => {
+                            let payload =
+                                serde_json::to_string(&RegistrationSuccess::new(token)).unwrap();
 
-                    Ok(Response::with(status::NoContent))
+                            Ok(Response::with((status::Ok, payload)))
+                        }
+                        Some(Err(reason)) => {
+                            let payload =
+                                serde_json::to_string(&RegistrationFailure::new(reason)).unwrap();
+
+                            Ok(Response::with((status::Conflict, payload)))
+                        }
+                        None => Ok(Response::with(status::ServiceUnavailable)),
+                    }
                 } else {
                     error!("unable to deserialize registation \"{}\"", body);
                     let reason = RegistrationFailure::new(format!(
@@ -0,0 +1,32 @@
+use std::{
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::Duration,
+};
+
+use iron::{status, Request, Response};
+use router::Router;
+
+use super::ask;
+use crate::simulation::communication::Message;
+
+pub fn router(tx: &Sender<Message>, timeout: Duration) -> Router {
+    let mut router = Router::new();
+
+    let scores_tx = tx.clone();
+    let scores_tx_mutex = Arc::new(Mutex::new(scores_tx));
+    router.get(
+        "/",
+        move |_: &mut Request| {
+            if let Some(scores) = ask(&scores_tx_mutex, timeout, Message::Scores) {
+                let payload = serde_json::to_string(&scores).unwrap();
+
+                Ok(Response::with((status::Ok, payload)))
+            } else {
+                Ok(Response::with(status::ServiceUnavailable))
+            }
+        },
+        "scores",
+    );
+
+    router
+}
@@ -0,0 +1,74 @@
+use std::{
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::Duration,
+};
+
+use iron::{status, Request, Response};
+use router::Router;
+
+use super::ask;
+use crate::simulation::communication::Message;
+
+pub fn router(tx: &Sender<Message>, timeout: Duration) -> Router {
+    let mut router = Router::new();
+
+    let health_tx = tx.clone();
+    let health_tx_mutex = Arc::new(Mutex::new(health_tx));
+    router.get(
+        "/:name/health",
+        move |request: &mut Request| {
+            let name = request
+                .extensions
+                .get::<Router>()
+                .and_then(|parameters| parameters.find("name"))
+                .unwrap_or("")
+                .to_string();
+            match ask(&health_tx_mutex, timeout, |reply_tx| {
+                Message::Health(name.clone(), reply_tx)
+            }) {
+                Some(Some(health)) => {
+                    let payload = serde_json::to_string(&health).unwrap();
+
+                    Ok(Response::with((status::Ok, payload)))
+                }
+                Some(None) => Ok(Response::with((
+                    status::NotFound,
+                    format!("unknown team \"{}\"", name),
+                ))),
+                None => Ok(Response::with(status::ServiceUnavailable)),
+            }
+        },
+        "health",
+    );
+
+    let errors_tx = tx.clone();
+    let errors_tx_mutex = Arc::new(Mutex::new(errors_tx));
+    router.get(
+        "/:name/errors",
+        move |request: &mut Request| {
+            let name = request
+                .extensions
+                .get::<Router>()
+                .and_then(|parameters| parameters.find("name"))
+                .unwrap_or("")
+                .to_string();
+            match ask(&errors_tx_mutex, timeout, |reply_tx| {
+                Message::BrainErrors(name.clone(), reply_tx)
+            }) {
+                Some(Some(errors)) => {
+                    let payload = serde_json::to_string(&errors).unwrap();
+
+                    Ok(Response::with((status::Ok, payload)))
+                }
+                Some(None) => Ok(Response::with((
+                    status::NotFound,
+                    format!("unknown team \"{}\"", name),
+                ))),
+                None => Ok(Response::with(status::ServiceUnavailable)),
+            }
+        },
+        "errors",
+    );
+
+    router
+}
@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    collision::Collisions,
+    energy::Metabolism,
+    event::Event,
+    flag::{Base, CaptureTheFlag},
+    food::{Foraging, Patch},
+    race::{Checkpoint, Progress, Race},
+    reproduction::Reproduction,
+    spatial::{Entry, SpatialIndex},
+    wind::Wind,
+    FlockId, Role, Teams,
+};
+
+#[derive(Default)]
+pub struct Arena {
+    pub collisions: Option<Collisions>,
+    pub predation: Option<Predation>,
+    pub foraging: Option<Foraging>,
+    pub capture_the_flag: Option<CaptureTheFlag>,
+    pub race: Option<Race>,
+    pub metabolism: Option<Metabolism>,
+    pub reproduction: Option<Reproduction>,
+    pub wind: Option<Wind>,
+    disabled: HashSet<Rule>,
+    index: SpatialIndex,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    Collisions,
+    Predation,
+    Foraging,
+    CaptureTheFlag,
+    Race,
+    Metabolism,
+    Reproduction,
+    Wind,
+}
+
+#[derive(Serialize)]
+pub struct World<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    food: Option<&'a [Patch]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bases: Option<&'a HashMap<String, Base>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkpoints: Option<&'a [Checkpoint]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    race: Option<&'a HashMap<String, Progress>>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn world(&self) -> World<'_> {
+        World {
+            food: self.foraging.as_ref().map(|foraging| foraging.patches()),
+            bases: self.capture_the_flag.as_ref().map(|game| game.bases()),
+            checkpoints: self.race.as_ref().map(|race| race.checkpoints()),
+            race: self.race.as_ref().map(|race| race.progress()),
+        }
+    }
+
+    pub fn toggle(&mut self, rule: Rule, enabled: bool) -> Result<(), String> {
+        let configured = match rule {
+            Rule::Collisions => self.collisions.is_some(),
+            Rule::Predation => self.predation.is_some(),
+            Rule::Foraging => self.foraging.is_some(),
+            Rule::CaptureTheFlag => self.capture_the_flag.is_some(),
+            Rule::Race => self.race.is_some(),
+            Rule::Metabolism => self.metabolism.is_some(),
+            Rule::Reproduction => self.reproduction.is_some(),
+            Rule::Wind => self.wind.is_some(),
+        };
+        if !configured {
+            return Err(format!("{:?} is not configured", rule));
+        }
+        if enabled {
+            self.disabled.remove(&rule);
+        } else {
+            self.disabled.insert(rule);
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self, teams: &mut Teams, tick: u64) -> Vec<Event> {
+        let disabled = &self.disabled;
+        let enabled = |rule| !disabled.contains(&rule);
+        let index = &mut self.index;
+        // Rules that move, remove or add boids leave the index stale for the
+        // rules after them.
+        let mut stale = true;
+
+        if let Some(wind) = self.wind.as_mut().filter(|_| enabled(Rule::Wind)) {
+            wind.step(teams);
+        }
+
+        let mut events = Vec::new();
+        if let Some(collisions) = self
+            .collisions
+            .as_mut()
+            .filter(|_| enabled(Rule::Collisions))
+        {
+            refresh(index, teams, &mut stale);
+            events.extend(collisions.step(teams, index));
+            stale = true;
+        }
+        if let Some(predation) = self.predation.as_ref().filter(|_| enabled(Rule::Predation)) {
+            refresh(index, teams, &mut stale);
+            let captures = predation.hunt(teams, index);
+            stale = !captures.is_empty();
+            events.extend(captures);
+        }
+        let meals = match self.foraging.as_mut().filter(|_| enabled(Rule::Foraging)) {
+            Some(foraging) => {
+                refresh(index, teams, &mut stale);
+                let meals = foraging.step(teams, index);
+                stale = stale || !meals.is_empty();
+                meals
+            }
+            None => Vec::new(),
+        };
+        if let Some(game) = self
+            .capture_the_flag
+            .as_mut()
+            .filter(|_| enabled(Rule::CaptureTheFlag))
+        {
+            refresh(index, teams, &mut stale);
+            events.extend(game.step(teams, index));
+            stale = true;
+        }
+        if let Some(race) = self.race.as_mut().filter(|_| enabled(Rule::Race)) {
+            events.extend(race.step(teams, tick));
+        }
+        if let Some(metabolism) = self
+            .metabolism
+            .as_ref()
+            .filter(|_| enabled(Rule::Metabolism))
+        {
+            let deaths = metabolism.step(teams, &meals);
+            stale = stale || !deaths.is_empty();
+            events.extend(deaths);
+        }
+        if let Some(reproduction) = self
+            .reproduction
+            .as_ref()
+            .filter(|_| enabled(Rule::Reproduction))
+        {
+            refresh(index, teams, &mut stale);
+            reproduction.step(teams, index);
+            stale = true;
+        }
+        refresh(index, teams, &mut stale);
+        events
+    }
+}
+
+fn refresh(index: &mut SpatialIndex, teams: &Teams, stale: &mut bool) {
+    if *stale {
+        index.rebuild(teams);
+        *stale = false;
+    }
+}
+
+pub struct Predation {
+    capture_radius: f64,
+}
+
+impl Predation {
+    pub fn new(capture_radius: f64) -> Self {
+        Self { capture_radius }
+    }
+
+    fn hunt(&self, teams: &mut Teams, index: &SpatialIndex) -> Vec<Event> {
+        let role_of = |entry: &Entry| teams.teams.get(index.team(entry)).map(|team| team.role);
+
+        let mut captured: HashSet<(usize, FlockId)> = HashSet::new();
+        let mut captures = Vec::new();
+        for predator in index.entries() {
+            if role_of(predator) != Some(Role::Predator) {
+                continue;
+            }
+            for prey in index.neighbours_within(predator.x, predator.y, self.capture_radius) {
+                if role_of(prey) == Some(Role::Prey) && captured.insert((prey.team, prey.id)) {
+                    captures.push((
+                        index.team(predator).to_string(),
+                        index.team(prey).to_string(),
+                        prey.id,
+                    ));
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        for (predator, prey, id) in captures {
+            let removed = teams
+                .teams
+                .get_mut(&prey)
+                .and_then(|team| team.flock.boids.remove(&id))
+                .is_some();
+            if removed {
+                if let Some(team) = teams.teams.get_mut(&predator) {
+                    team.captures += 1;
+                }
+                events.push(Event::Capture {
+                    predator,
+                    prey,
+                    boid: id,
+                });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{collision::Response, flag::CaptureTheFlag, Boid, Team};
+
+    fn team(name: &str, role: Role, boids: Vec<(u64, Boid)>) -> Team {
+        let mut team = Team::new(name, "TEST ADDRESS", 2643);
+        team.role = role;
+        for (id, boid) in boids {
+            team.flock.boids.insert(FlockId::from(id), boid);
+        }
+        team
+    }
+
+    #[test]
+    fn prey_within_capture_radius_should_be_captured() {
+        let mut teams = Teams::new();
+        teams.teams.insert(
+            "WOLF".to_owned(),
+            team("WOLF", Role::Predator, vec![(1, Boid::new(0.5, 0.5, 0.0, 0.0))]),
+        );
+        teams.teams.insert(
+            "SHEEP".to_owned(),
+            team(
+                "SHEEP",
+                Role::Prey,
+                vec![
+                    (2, Boid::new(0.505, 0.5, 0.0, 0.0)),
+                    (3, Boid::new(0.9, 0.9, 0.0, 0.0)),
+                ],
+            ),
+        );
+        let mut arena = Arena::new();
+        arena.predation = Some(Predation::new(0.01));
+
+        let events = arena.step(&mut teams, 1);
+
+        assert_eq!(
+            events,
+            vec![Event::Capture {
+                predator: "WOLF".to_owned(),
+                prey: "SHEEP".to_owned(),
+                boid: FlockId::from(2),
+            }]
+        );
+        assert_eq!(teams.teams["SHEEP"].flock.boids.len(), 1);
+        assert_eq!(teams.teams["WOLF"].captures, 1);
+    }
+
+    #[test]
+    fn predation_should_see_boids_where_collisions_pushed_them() {
+        let mut teams = Teams::new();
+        teams.teams.insert(
+            "WOLF".to_owned(),
+            team("WOLF", Role::Predator, vec![(1, Boid::new(0.489, 0.5, 0.0, 0.0))]),
+        );
+        teams.teams.insert(
+            "SHEEP".to_owned(),
+            team(
+                "SHEEP",
+                Role::Prey,
+                vec![
+                    (2, Boid::new(0.5, 0.5, 0.0, 0.0)),
+                    (3, Boid::new(0.505, 0.5, 0.0, 0.0)),
+                ],
+            ),
+        );
+        let mut arena = Arena::new();
+        arena.collisions = Some(Collisions::new(0.005, Response::Push));
+        arena.predation = Some(Predation::new(0.009));
+
+        let events = arena.step(&mut teams, 1);
+
+        assert!(events.contains(&Event::Capture {
+            predator: "WOLF".to_owned(),
+            prey: "SHEEP".to_owned(),
+            boid: FlockId::from(2),
+        }));
+    }
+
+    #[test]
+    fn reproduction_should_see_boids_where_they_were_tagged_home() {
+        let mut teams = Teams::new();
+        teams.teams.insert(
+            "BLUE".to_owned(),
+            team(
+                "BLUE",
+                Role::Prey,
+                vec![
+                    (1, Boid::new(0.25, 0.5, 0.0, 0.0)),
+                    (2, Boid::new(0.855, 0.5, 0.0, 0.0)),
+                ],
+            ),
+        );
+        teams.teams.insert(
+            "RED".to_owned(),
+            team("RED", Role::Prey, vec![(3, Boid::new(0.255, 0.5, 0.0, 0.0))]),
+        );
+        let mut arena = Arena::new();
+        arena.capture_the_flag = Some(CaptureTheFlag::new(0.05, 0.02));
+        arena.reproduction = Some(Reproduction::new(1f64).mate_radius(Some(0.01)));
+
+        let events = arena.step(&mut teams, 1);
+
+        assert!(events.contains(&Event::Tagged {
+            team: "BLUE".to_owned(),
+            by: "RED".to_owned(),
+            boid: FlockId::from(1),
+        }));
+        assert_eq!(teams.teams["BLUE"].flock.boids.len(), 4);
+        assert_eq!(teams.teams["RED"].flock.boids.len(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    str::FromStr,
+};
+
+use super::{event::Event, geometry::delta, spatial::SpatialIndex, FlockId, Teams};
+
+pub struct Collisions {
+    radius: f64,
+    response: Response,
+    colliding: HashSet<(Key, Key)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Response {
+    Push,
+    Stun(u32),
+    Remove,
+}
+
+impl FromStr for Response {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "push" => Ok(Response::Push),
+            "remove" => Ok(Response::Remove),
+            _ => source
+                .strip_prefix("stun:")
+                .and_then(|ticks| ticks.parse::<u32>().ok())
+                .map(Response::Stun)
+                .ok_or_else(|| format!("unknown collision response \"{}\"", source)),
+        }
+    }
+}
+
+type Key = (String, FlockId);
+
+#[derive(Default)]
+struct Adjustment {
+    dx: f64,
+    dy: f64,
+    vx: f64,
+    vy: f64,
+}
+
+impl Collisions {
+    pub fn new(radius: f64, response: Response) -> Self {
+        Self {
+            radius,
+            response,
+            colliding: HashSet::new(),
+        }
+    }
+
+    /// Resolves the collisions of this tick. Boids that keep overlapping are
+    /// pushed apart every tick, but only crash once, when they first touch.
+    pub fn step(&mut self, teams: &mut Teams, index: &SpatialIndex) -> Vec<Event> {
+        let mut pairs: Vec<(Key, Key)> = index
+            .pairs_within(2f64 * self.radius)
+            .into_iter()
+            .map(|(entry, other)| {
+                let key = (index.team(entry).to_string(), entry.id);
+                let other = (index.team(other).to_string(), other.id);
+                if key <= other {
+                    (key, other)
+                } else {
+                    (other, key)
+                }
+            })
+            .collect();
+        pairs.sort();
+        let previous = mem::replace(&mut self.colliding, pairs.iter().cloned().collect());
+        let crashes: Vec<&(Key, Key)> = pairs
+            .iter()
+            .filter(|pair| !previous.contains(*pair))
+            .collect();
+        if pairs.is_empty() {
+            return Vec::new();
+        }
+
+        let events = crashes
+            .iter()
+            .map(|(key, other)| Event::Crash {
+                team: key.0.clone(),
+                boid: key.1,
+                other_team: other.0.clone(),
+                other_boid: other.1,
+            })
+            .collect();
+
+        match self.response {
+            Response::Push => {
+                let state: HashMap<&Key, (f64, f64, f64, f64)> = pairs
+                    .iter()
+                    .flat_map(|(key, other)| vec![key, other])
+                    .filter_map(|key| {
+                        teams
+                            .teams
+                            .get(&key.0)
+                            .and_then(|team| team.flock.boids.get(&key.1))
+                            .map(|boid| {
+                                let vx = boid.speed * boid.heading.cos();
+                                let vy = boid.speed * boid.heading.sin();
+                                (key, (boid.x, boid.y, vx, vy))
+                            })
+                    })
+                    .collect();
+                let mut adjustments: HashMap<&Key, Adjustment> = HashMap::new();
+                for (key, other) in &pairs {
+                    let ((x, y, vx, vy), (ox, oy, ovx, ovy)) =
+                        match (state.get(key), state.get(other)) {
+                            (Some(boid), Some(other_boid)) => (*boid, *other_boid),
+                            _ => continue,
+                        };
+                    let (nx, ny) = (delta(x, ox), delta(y, oy));
+                    let d = (nx * nx + ny * ny).sqrt();
+                    let (nx, ny) = if d > 0f64 {
+                        (nx / d, ny / d)
+                    } else {
+                        (1f64, 0f64)
+                    };
+                    let overlap = (2f64 * self.radius - d) / 2f64;
+                    let approach = (vx - ovx) * nx + (vy - ovy) * ny;
+                    let exchange = if approach > 0f64 { approach } else { 0f64 };
+
+                    let adjustment = adjustments.entry(key).or_default();
+                    adjustment.dx -= overlap * nx;
+                    adjustment.dy -= overlap * ny;
+                    adjustment.vx -= exchange * nx;
+                    adjustment.vy -= exchange * ny;
+                    let adjustment = adjustments.entry(other).or_default();
+                    adjustment.dx += overlap * nx;
+                    adjustment.dy += overlap * ny;
+                    adjustment.vx += exchange * nx;
+                    adjustment.vy += exchange * ny;
+                }
+                for ((name, id), adjustment) in adjustments {
+                    if let Some(boid) = teams
+                        .teams
+                        .get_mut(name)
+                        .and_then(|team| team.flock.boids.get_mut(id))
+                    {
+                        boid.push(adjustment.dx, adjustment.dy, adjustment.vx, adjustment.vy);
+                    }
+                }
+            }
+            Response::Stun(ticks) => {
+                for (name, id) in crashes.iter().flat_map(|(key, other)| vec![key, other]) {
+                    if let Some(boid) = teams
+                        .teams
+                        .get_mut(name)
+                        .and_then(|team| team.flock.boids.get_mut(id))
+                    {
+                        boid.stunned = ticks;
+                    }
+                }
+            }
+            Response::Remove => {
+                for (name, id) in pairs.iter().flat_map(|(key, other)| vec![key, other]) {
+                    if let Some(team) = teams.teams.get_mut(name) {
+                        team.flock.boids.remove(id);
+                    }
+                }
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{geometry::distance, Boid, Role, Team};
+
+    fn teams() -> Teams {
+        let mut teams = Teams::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(0.5, 0.5, 0.0, 0.01));
+        team.flock
+            .boids
+            .insert(FlockId::from(2), Boid::new(0.505, 0.5, 3.0, 0.01));
+        teams.teams.insert("TEST".to_owned(), team);
+        teams
+    }
+
+    #[test]
+    fn overlapping_boids_should_be_pushed_apart() {
+        let mut teams = teams();
+        let mut collisions = Collisions::new(0.005, Response::Push);
+        let index = SpatialIndex::of(&teams, 0.02);
+
+        let events = collisions.step(&mut teams, &index);
+
+        assert_eq!(events.len(), 1);
+        let boids = &teams.teams["TEST"].flock.boids;
+        let (first, second) = (&boids[&FlockId::from(1)], &boids[&FlockId::from(2)]);
+        assert!(distance(first.x, first.y, second.x, second.y) >= 0.01 - 1e-9);
+        assert!(first.heading.cos() < 0f64);
+    }
+
+    #[test]
+    fn crashed_boids_should_be_removed() {
+        let mut teams = teams();
+        let mut collisions = Collisions::new(0.005, Response::Remove);
+        let index = SpatialIndex::of(&teams, 0.02);
+
+        collisions.step(&mut teams, &index);
+
+        assert!(teams.teams["TEST"].flock.is_empty());
+    }
+
+    #[test]
+    fn stunned_boids_should_recover() {
+        let mut teams = teams();
+        let mut collisions = Collisions::new(0.005, Response::Stun(2));
+        let phenotype = Role::Prey.phenotype();
+
+        for _ in 0..3 {
+            let index = SpatialIndex::of(&teams, 0.02);
+            collisions.step(&mut teams, &index);
+            for boid in teams.teams.get_mut("TEST").unwrap().flock.boids.values_mut() {
+                boid.step(1f64, &phenotype);
+            }
+        }
+
+        let boids = &teams.teams["TEST"].flock.boids;
+        assert!(boids.values().all(|boid| boid.stunned == 0));
+    }
+}
@@ -1,8 +1,21 @@
-use super::{Intentions, Registration, Unregistration};
+use std::{collections::HashMap, sync::mpsc::Sender};
+
+use serde_json::Value;
+
+use crate::brain::Diagnostic;
+
+use super::{
+    arena::Rule,
+    health::Health,
+    leaderboard::{Leaderboard, Standing},
+    score::Scores,
+    FlockId, Intentions, Registration, Role, Unregistration,
+};
 
 #[derive(Debug)]
 pub enum Message {
-    Register(Registration),
+    Register(Registration, Sender<Result<String, String>>),
+    Authenticate(String, String, Sender<bool>),
     Unregister(Unregistration),
     Heartbeat,
     HeartbeatStatus((String, bool)),
@@ -10,4 +23,40 @@ pub enum Message {
     SpawnAll(usize),
     Spawn((String, usize)),
     BrainUpdate(String, Intentions),
+    BrainError(String, Diagnostic),
+    BrainErrors(String, Sender<Option<Vec<Diagnostic>>>),
+    Scores(Sender<HashMap<String, Scores>>),
+    Standings(Sender<Vec<Standing>>),
+    Leaderboard(Sender<Leaderboard>),
+    Health(String, Sender<Option<Health>>),
+    Order(Order, Sender<Result<(), String>>),
+    State(Sender<Value>),
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum Order {
+    Spawn {
+        team: String,
+        count: usize,
+        position: Option<(f64, f64)>,
+    },
+    SpawnAll {
+        count: usize,
+    },
+    Kill {
+        team: String,
+        boids: Vec<FlockId>,
+    },
+    Clear {
+        team: String,
+    },
+    Toggle {
+        rule: Rule,
+        enabled: bool,
+    },
+    Assign {
+        team: String,
+        role: Role,
+    },
 }
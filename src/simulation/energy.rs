@@ -0,0 +1,144 @@
+use super::{
+    event::{Cause, Event},
+    FlockId, Teams,
+};
+
+const REST_SPEED: f64 = 0.001;
+
+pub struct Metabolism {
+    speed_cost: f64,
+    turn_cost: f64,
+    rest_regeneration: f64,
+    food_energy: f64,
+    max_age: Option<u64>,
+}
+
+impl Metabolism {
+    pub fn new() -> Self {
+        Self {
+            speed_cost: 0.1,
+            turn_cost: 0.001,
+            rest_regeneration: 0.002,
+            food_energy: 0.25,
+            max_age: None,
+        }
+    }
+
+    pub fn speed_cost(mut self, speed_cost: f64) -> Self {
+        self.speed_cost = speed_cost;
+        self
+    }
+
+    pub fn turn_cost(mut self, turn_cost: f64) -> Self {
+        self.turn_cost = turn_cost;
+        self
+    }
+
+    pub fn rest_regeneration(mut self, rest_regeneration: f64) -> Self {
+        self.rest_regeneration = rest_regeneration;
+        self
+    }
+
+    pub fn food_energy(mut self, food_energy: f64) -> Self {
+        self.food_energy = food_energy;
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Option<u64>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn step(&self, teams: &mut Teams, meals: &[(String, FlockId)]) -> Vec<Event> {
+        for (name, id) in meals {
+            if let Some(boid) = teams
+                .teams
+                .get_mut(name)
+                .and_then(|team| team.flock.boids.get_mut(id))
+            {
+                boid.energy += self.food_energy;
+            }
+        }
+
+        let mut events = Vec::new();
+        for (name, team) in teams.teams.iter_mut() {
+            let mut dead = Vec::new();
+            for (id, boid) in team.flock.boids.iter_mut() {
+                boid.energy -= self.speed_cost * boid.speed + self.turn_cost * boid.turned;
+                if boid.speed < REST_SPEED {
+                    boid.energy += self.rest_regeneration;
+                }
+                boid.energy = boid.energy.min(1f64);
+
+                if boid.energy <= 0f64 {
+                    boid.energy = 0f64;
+                    dead.push((*id, Cause::Starvation));
+                } else if self.max_age.is_some_and(|max_age| boid.age >= max_age) {
+                    dead.push((*id, Cause::OldAge));
+                }
+            }
+            for (id, cause) in dead {
+                team.flock.boids.remove(&id);
+                events.push(Event::Death {
+                    team: name.clone(),
+                    boid: id,
+                    cause,
+                });
+            }
+        }
+        events
+    }
+}
+
+impl Default for Metabolism {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Boid, Team};
+
+    fn teams_with(boid: Boid) -> Teams {
+        let mut teams = Teams::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock.boids.insert(FlockId::from(1), boid);
+        teams.teams.insert("TEST".to_owned(), team);
+        teams
+    }
+
+    #[test]
+    fn exhausted_boids_should_starve() {
+        let mut boid = Boid::new(0.5, 0.5, 0.0, 0.01);
+        boid.energy = 0.0005;
+        let mut teams = teams_with(boid);
+        let metabolism = Metabolism::new();
+
+        let events = metabolism.step(&mut teams, &[]);
+
+        assert_eq!(
+            events,
+            vec![Event::Death {
+                team: "TEST".to_owned(),
+                boid: FlockId::from(1),
+                cause: Cause::Starvation,
+            }]
+        );
+        assert!(teams.teams["TEST"].flock.is_empty());
+    }
+
+    #[test]
+    fn resting_boids_should_regenerate_and_eating_boids_should_be_fed() {
+        let mut boid = Boid::new(0.5, 0.5, 0.0, 0.0);
+        boid.energy = 0.5;
+        let mut teams = teams_with(boid);
+        let metabolism = Metabolism::new().rest_regeneration(0.1).food_energy(0.2);
+
+        metabolism.step(&mut teams, &[("TEST".to_owned(), FlockId::from(1))]);
+
+        let energy = teams.teams["TEST"].flock.boids[&FlockId::from(1)].energy;
+        assert!((energy - 0.8).abs() < 1e-9);
+    }
+}
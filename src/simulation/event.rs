@@ -0,0 +1,51 @@
+use super::FlockId;
+use crate::brain::Diagnostic;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum Event {
+    Capture {
+        predator: String,
+        prey: String,
+        boid: FlockId,
+    },
+    FlagTaken {
+        team: String,
+        flag: String,
+        boid: FlockId,
+    },
+    FlagCaptured {
+        team: String,
+        flag: String,
+        boid: FlockId,
+    },
+    Tagged {
+        team: String,
+        by: String,
+        boid: FlockId,
+    },
+    Finished {
+        team: String,
+        tick: u64,
+    },
+    Crash {
+        team: String,
+        boid: FlockId,
+        other_team: String,
+        other_boid: FlockId,
+    },
+    Death {
+        team: String,
+        boid: FlockId,
+        cause: Cause,
+    },
+    BrainError {
+        team: String,
+        diagnostic: Diagnostic,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum Cause {
+    Starvation,
+    OldAge,
+}
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, f64::consts::PI};
+
+use super::{event::Event, geometry::distance, spatial::SpatialIndex, FlockId, Teams};
+
+const BASE_DISTANCE: f64 = 0.35;
+
+pub struct CaptureTheFlag {
+    bases: HashMap<String, Base>,
+    base_radius: f64,
+    tag_radius: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Base {
+    x: f64,
+    y: f64,
+    radius: f64,
+    flag: Flag,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum Flag {
+    Home,
+    Carried { team: String, boid: FlockId },
+}
+
+impl CaptureTheFlag {
+    pub fn new(base_radius: f64, tag_radius: f64) -> Self {
+        Self {
+            bases: HashMap::new(),
+            base_radius,
+            tag_radius,
+        }
+    }
+
+    pub fn bases(&self) -> &HashMap<String, Base> {
+        &self.bases
+    }
+
+    pub fn step(&mut self, teams: &mut Teams, index: &SpatialIndex) -> Vec<Event> {
+        self.place_bases(teams);
+        self.return_lost_flags(teams);
+
+        let mut events = Vec::new();
+        events.extend(self.pick_up(teams));
+        events.extend(self.bring_home(teams));
+        events.extend(self.tag(teams, index));
+        events
+    }
+
+    fn place_bases(&mut self, teams: &Teams) {
+        let mut names: Vec<&String> = teams.teams.keys().collect();
+        names.sort();
+        if names.len() == self.bases.len()
+            && names.iter().all(|name| self.bases.contains_key(*name))
+        {
+            return;
+        }
+
+        let n = names.len() as f64;
+        let bases = names
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let angle = 2f64 * PI * index as f64 / n;
+                let flag = self
+                    .bases
+                    .get(name)
+                    .map(|base| base.flag.clone())
+                    .unwrap_or(Flag::Home);
+                let base = Base {
+                    x: 0.5 + BASE_DISTANCE * angle.cos(),
+                    y: 0.5 + BASE_DISTANCE * angle.sin(),
+                    radius: self.base_radius,
+                    flag,
+                };
+                (name.clone(), base)
+            })
+            .collect();
+        self.bases = bases;
+    }
+
+    fn return_lost_flags(&mut self, teams: &Teams) {
+        for base in self.bases.values_mut() {
+            if let Flag::Carried { team, boid } = &base.flag {
+                let carried = teams
+                    .teams
+                    .get(team)
+                    .and_then(|team| team.flock.boids.get(boid))
+                    .map(|boid| boid.carrying.is_some())
+                    .unwrap_or(false);
+                if !carried {
+                    base.flag = Flag::Home;
+                }
+            }
+        }
+    }
+
+    fn pick_up(&mut self, teams: &mut Teams) -> Vec<Event> {
+        let mut events = Vec::new();
+        for (name, team) in teams.teams.iter_mut() {
+            for (id, boid) in team.flock.boids.iter_mut() {
+                if boid.carrying.is_some() {
+                    continue;
+                }
+                let reached = self.bases.iter_mut().find(|(owner, base)| {
+                    *owner != name
+                        && base.flag == Flag::Home
+                        && distance(base.x, base.y, boid.x, boid.y) <= base.radius
+                });
+                if let Some((owner, base)) = reached {
+                    boid.carrying = Some(owner.clone());
+                    base.flag = Flag::Carried {
+                        team: name.clone(),
+                        boid: *id,
+                    };
+                    events.push(Event::FlagTaken {
+                        team: name.clone(),
+                        flag: owner.clone(),
+                        boid: *id,
+                    });
+                }
+            }
+        }
+        events
+    }
+
+    fn bring_home(&mut self, teams: &mut Teams) -> Vec<Event> {
+        let mut events = Vec::new();
+        for (name, team) in teams.teams.iter_mut() {
+            let home = match self.bases.get(name) {
+                Some(base) => (base.x, base.y, base.radius),
+                None => continue,
+            };
+            let mut captured = 0;
+            for (id, boid) in team.flock.boids.iter_mut() {
+                if boid.carrying.is_none() || distance(home.0, home.1, boid.x, boid.y) > home.2 {
+                    continue;
+                }
+                let flag = boid.carrying.take().unwrap(/* safe because of the is_none check */);
+                if let Some(base) = self.bases.get_mut(&flag) {
+                    base.flag = Flag::Home;
+                }
+                captured += 1;
+                events.push(Event::FlagCaptured {
+                    team: name.clone(),
+                    flag,
+                    boid: *id,
+                });
+            }
+            team.flags += captured;
+        }
+        events
+    }
+
+    fn tag(&mut self, teams: &mut Teams, index: &SpatialIndex) -> Vec<Event> {
+        let mut events = Vec::new();
+        for (name, team) in teams.teams.iter_mut() {
+            let home = match self.bases.get(name) {
+                Some(base) => (base.x, base.y),
+                None => continue,
+            };
+            for (id, boid) in team.flock.boids.iter_mut() {
+                let owner = match self.territory(boid.x, boid.y) {
+                    Some(owner) if owner != name => owner.clone(),
+                    _ => continue,
+                };
+                let tagged = index
+                    .neighbours_within(boid.x, boid.y, self.tag_radius)
+                    .into_iter()
+                    .any(|defender| {
+                        index.team(defender) == owner
+                            && self.territory(defender.x, defender.y) == Some(&owner)
+                    });
+                if tagged {
+                    if let Some(flag) = boid.carrying.take() {
+                        if let Some(base) = self.bases.get_mut(&flag) {
+                            base.flag = Flag::Home;
+                        }
+                    }
+                    boid.x = home.0;
+                    boid.y = home.1;
+                    events.push(Event::Tagged {
+                        team: name.clone(),
+                        by: owner,
+                        boid: *id,
+                    });
+                }
+            }
+        }
+        events
+    }
+
+    fn territory(&self, x: f64, y: f64) -> Option<&String> {
+        self.bases
+            .iter()
+            .map(|(name, base)| (name, distance(base.x, base.y, x, y)))
+            .min_by(|(_, left), (_, right)| left.total_cmp(right))
+            .map(|(name, _)| name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Boid, Team};
+
+    fn teams() -> Teams {
+        let mut teams = Teams::new();
+        for (index, name) in ["A", "B"].iter().enumerate() {
+            let team = Team::new(*name, "TEST ADDRESS", 2643 + index as u16);
+            teams.teams.insert(name.to_string(), team);
+        }
+        teams
+    }
+
+    fn step(game: &mut CaptureTheFlag, teams: &mut Teams) -> Vec<Event> {
+        let index = SpatialIndex::of(teams, 0.02);
+        game.step(teams, &index)
+    }
+
+    #[test]
+    fn flag_should_be_picked_up_and_brought_home() {
+        let mut teams = teams();
+        let mut game = CaptureTheFlag::new(0.05, 0.01);
+        step(&mut game, &mut teams);
+        let (bx, by) = (game.bases["B"].x, game.bases["B"].y);
+        teams
+            .teams
+            .get_mut("A")
+            .unwrap()
+            .flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(bx, by, 0.0, 0.0));
+
+        let events = step(&mut game, &mut teams);
+
+        assert_eq!(
+            events,
+            vec![Event::FlagTaken {
+                team: "A".to_owned(),
+                flag: "B".to_owned(),
+                boid: FlockId::from(1),
+            }]
+        );
+
+        let (ax, ay) = (game.bases["A"].x, game.bases["A"].y);
+        {
+            let boid = teams
+                .teams
+                .get_mut("A")
+                .unwrap()
+                .flock
+                .boids
+                .get_mut(&FlockId::from(1))
+                .unwrap();
+            boid.x = ax;
+            boid.y = ay;
+        }
+
+        let events = step(&mut game, &mut teams);
+
+        assert_eq!(
+            events,
+            vec![Event::FlagCaptured {
+                team: "A".to_owned(),
+                flag: "B".to_owned(),
+                boid: FlockId::from(1),
+            }]
+        );
+        assert_eq!(teams.teams["A"].flags, 1);
+        assert_eq!(game.bases["B"].flag, Flag::Home);
+    }
+
+    #[test]
+    fn boid_tagged_in_enemy_territory_should_be_sent_home() {
+        let mut teams = teams();
+        let mut game = CaptureTheFlag::new(0.05, 0.01);
+        step(&mut game, &mut teams);
+        let (bx, by) = (game.bases["B"].x + 0.1, game.bases["B"].y);
+        teams
+            .teams
+            .get_mut("A")
+            .unwrap()
+            .flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(bx, by, 0.0, 0.0));
+        teams
+            .teams
+            .get_mut("B")
+            .unwrap()
+            .flock
+            .boids
+            .insert(FlockId::from(2), Boid::new(bx + 0.005, by, 0.0, 0.0));
+
+        let events = step(&mut game, &mut teams);
+
+        assert_eq!(
+            events,
+            vec![Event::Tagged {
+                team: "A".to_owned(),
+                by: "B".to_owned(),
+                boid: FlockId::from(1),
+            }]
+        );
+        let boid = &teams.teams["A"].flock.boids[&FlockId::from(1)];
+        assert_eq!((boid.x, boid.y), (game.bases["A"].x, game.bases["A"].y));
+    }
+}
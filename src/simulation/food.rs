@@ -0,0 +1,162 @@
+use std::collections::{HashMap, HashSet};
+
+use random::{self, Source};
+
+use super::{spatial::SpatialIndex, FlockId, Spawn, Teams};
+
+pub struct Foraging {
+    patches: Vec<Patch>,
+    spawn_rate: f64,
+    max_patches: usize,
+    patch_size: f64,
+    patch_radius: f64,
+    regrowth: f64,
+    offspring: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Patch {
+    x: f64,
+    y: f64,
+    radius: f64,
+    food: f64,
+}
+
+impl Foraging {
+    pub fn new(spawn_rate: f64) -> Self {
+        Self {
+            patches: Vec::new(),
+            spawn_rate,
+            max_patches: 10,
+            patch_size: 10f64,
+            patch_radius: 0.02,
+            regrowth: 0f64,
+            offspring: 0,
+        }
+    }
+
+    pub fn max_patches(mut self, max_patches: usize) -> Self {
+        self.max_patches = max_patches;
+        self
+    }
+
+    pub fn patch_size(mut self, patch_size: f64) -> Self {
+        self.patch_size = patch_size;
+        self
+    }
+
+    pub fn patch_radius(mut self, patch_radius: f64) -> Self {
+        self.patch_radius = patch_radius;
+        self
+    }
+
+    pub fn regrowth(mut self, regrowth: f64) -> Self {
+        self.regrowth = regrowth;
+        self
+    }
+
+    pub fn offspring(mut self, offspring: usize) -> Self {
+        self.offspring = offspring;
+        self
+    }
+
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    pub fn step(&mut self, teams: &mut Teams, index: &SpatialIndex) -> Vec<(String, FlockId)> {
+        let mut source = random::default();
+        if self.patches.len() < self.max_patches && source.read_f64() < self.spawn_rate {
+            self.spawn(1);
+        }
+
+        let patch_size = self.patch_size;
+        let regrowth = self.regrowth;
+        self.patches
+            .iter_mut()
+            .for_each(|patch| patch.food = (patch.food + regrowth).min(patch_size));
+
+        let mut eaten: HashSet<(usize, FlockId)> = HashSet::new();
+        let mut meals: HashMap<String, Vec<(FlockId, f64, f64)>> = HashMap::new();
+        for patch in self.patches.iter_mut() {
+            for entry in index.neighbours_within(patch.x, patch.y, patch.radius) {
+                if patch.food < 1f64 {
+                    break;
+                }
+                let alive = teams
+                    .teams
+                    .get(index.team(entry))
+                    .map(|team| team.flock.boids.contains_key(&entry.id))
+                    .unwrap_or(false);
+                if alive && eaten.insert((entry.team, entry.id)) {
+                    patch.food -= 1f64;
+                    meals
+                        .entry(index.team(entry).to_string())
+                        .or_default()
+                        .push((entry.id, entry.x, entry.y));
+                }
+            }
+        }
+
+        let mut fed = Vec::new();
+        for (name, positions) in meals {
+            if let Some(team) = teams.teams.get_mut(&name) {
+                team.food += positions.len() as u64;
+                for (id, x, y) in positions {
+                    team.flock.spawn_near(x, y, self.offspring);
+                    fed.push((name.clone(), id));
+                }
+            }
+        }
+
+        if regrowth <= 0f64 {
+            self.patches.retain(|patch| patch.food >= 1f64);
+        }
+        fed
+    }
+}
+
+impl Spawn for Foraging {
+    fn spawn(&mut self, n: usize) {
+        let mut source = random::default();
+        for _ in 0..n {
+            self.patches.push(Patch {
+                x: source.read_f64(),
+                y: source.read_f64(),
+                radius: self.patch_radius,
+                food: self.patch_size,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Boid, Team};
+
+    #[test]
+    fn boid_reaching_a_patch_should_consume_food() {
+        let mut teams = Teams::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(0.5, 0.5, 0.0, 0.0));
+        teams.teams.insert("TEST".to_owned(), team);
+        let mut foraging = Foraging::new(0f64).offspring(2);
+        foraging.patches.push(Patch {
+            x: 0.51,
+            y: 0.5,
+            radius: 0.02,
+            food: 5f64,
+        });
+
+        let index = SpatialIndex::of(&teams, 0.02);
+
+        foraging.step(&mut teams, &index);
+
+        assert_eq!(foraging.patches[0].food, 4f64);
+        assert_eq!(teams.teams["TEST"].food, 1);
+        assert_eq!(teams.teams["TEST"].flock.boids.len(), 3);
+    }
+}
@@ -0,0 +1,39 @@
+use std::f64::consts::PI;
+
+/// Shortest signed difference from `from` to `to` on the unit circle.
+pub fn delta(from: f64, to: f64) -> f64 {
+    let mut d = (to - from) % 1f64;
+    if d > 0.5 {
+        d -= 1f64;
+    }
+    if d < -0.5 {
+        d += 1f64;
+    }
+    d
+}
+
+/// Distance between two points in the unit torus that wraps around its edges.
+pub fn distance(x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+    let dx = delta(x0, x1);
+    let dy = delta(y0, y1);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Mean of coordinates on the unit circle, i.e. taking the wrap-around into account.
+pub fn circular_mean<I>(coordinates: I) -> Option<f64>
+where
+    I: IntoIterator<Item = f64>,
+{
+    let (mut cos, mut sin, mut n) = (0f64, 0f64, 0usize);
+    for coordinate in coordinates {
+        let angle = 2f64 * PI * coordinate;
+        cos += angle.cos();
+        sin += angle.sin();
+        n += 1;
+    }
+    if n == 0 {
+        None
+    } else {
+        Some((sin.atan2(cos) / (2f64 * PI)).rem_euclid(1f64))
+    }
+}
@@ -0,0 +1,18 @@
+use super::FlockId;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum Warning {
+    UnknownBoid { boid: FlockId },
+    WrongControl { boid: FlockId },
+    NotFinite { boid: FlockId },
+    Clamped { boid: FlockId },
+    Missing { boids: usize },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Health {
+    pub name: String,
+    pub connected: bool,
+    pub boids: usize,
+    pub warnings: Vec<Warning>,
+}
@@ -0,0 +1,222 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+};
+
+use super::{score::Scores, Teams};
+
+/// Number of samples kept per team.
+pub const HISTORY_LENGTH: usize = 1000;
+
+#[derive(Serialize, Clone)]
+pub struct Leaderboard {
+    interval: u64,
+    last_sample: Option<u64>,
+    standings: Vec<Standing>,
+    history_length: usize,
+    history: HashMap<String, VecDeque<Sample>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Standing {
+    team: String,
+    rank: usize,
+    change: i64,
+    scores: Scores,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Sample {
+    tick: u64,
+    rank: usize,
+    scores: Scores,
+}
+
+impl Leaderboard {
+    pub fn new(interval: u64) -> Self {
+        Self {
+            interval,
+            last_sample: None,
+            standings: Vec::new(),
+            history_length: HISTORY_LENGTH,
+            history: HashMap::new(),
+        }
+    }
+
+    /// Keeps only the latest `length` samples of each team.
+    pub fn history_length(mut self, length: usize) -> Self {
+        self.history_length = length.max(1);
+        self
+    }
+
+    pub fn record(&mut self, tick: u64, teams: &Teams) {
+        if let Some(last_sample) = self.last_sample {
+            if tick < last_sample + self.interval {
+                return;
+            }
+        }
+        self.last_sample = Some(tick);
+
+        let mut ranking: Vec<(&String, &Scores)> = teams
+            .teams
+            .iter()
+            .map(|(name, team)| (name, &team.scores))
+            .collect();
+        ranking.sort_by(|(left_name, left), (right_name, right)| {
+            right
+                .aggregate
+                .partial_cmp(&left.aggregate)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| left_name.cmp(right_name))
+        });
+
+        let previous: HashMap<String, usize> = self
+            .standings
+            .iter()
+            .map(|standing| (standing.team.clone(), standing.rank))
+            .collect();
+        self.standings = ranking
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, scores))| {
+                let rank = index + 1;
+                let change = previous
+                    .get(name)
+                    .map(|&previous_rank| previous_rank as i64 - rank as i64)
+                    .unwrap_or(0);
+                Standing {
+                    team: name.clone(),
+                    rank,
+                    change,
+                    scores: scores.clone(),
+                }
+            })
+            .collect();
+
+        for standing in &self.standings {
+            let samples = self.history.entry(standing.team.clone()).or_default();
+            if samples.len() >= self.history_length {
+                samples.pop_front();
+            }
+            samples.push_back(Sample {
+                tick,
+                rank: standing.rank,
+                scores: standing.scores.clone(),
+            });
+        }
+    }
+
+    pub fn standings(&self) -> &[Standing] {
+        &self.standings
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut metrics: Vec<&String> = self
+            .history
+            .values()
+            .flat_map(|samples| samples.iter())
+            .flat_map(|sample| sample.scores.metrics.keys())
+            .collect();
+        metrics.sort();
+        metrics.dedup();
+
+        let mut rows: Vec<(&String, &Sample)> = self
+            .history
+            .iter()
+            .flat_map(|(team, samples)| samples.iter().map(move |sample| (team, sample)))
+            .collect();
+        rows.sort_by(|(left_team, left), (right_team, right)| {
+            left.tick
+                .cmp(&right.tick)
+                .then_with(|| left.rank.cmp(&right.rank))
+                .then_with(|| left_team.cmp(right_team))
+        });
+
+        let mut csv = String::from("tick,team,rank,aggregate");
+        for metric in &metrics {
+            csv.push(',');
+            csv.push_str(&escape(metric));
+        }
+        csv.push('\n');
+        for (team, sample) in rows {
+            csv.push_str(&format!(
+                "{},{},{},{}",
+                sample.tick,
+                escape(team),
+                sample.rank,
+                sample.scores.aggregate
+            ));
+            for metric in &metrics {
+                csv.push(',');
+                if let Some(value) = sample.scores.metrics.get(*metric) {
+                    csv.push_str(&value.to_string());
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn escape(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Team;
+
+    fn teams_with(aggregates: &[(&str, f64)]) -> Teams {
+        let mut teams = Teams::new();
+        for (index, (name, aggregate)) in aggregates.iter().enumerate() {
+            let mut team = Team::new(*name, "TEST ADDRESS", 2643 + index as u16);
+            team.scores.aggregate = *aggregate;
+            teams.teams.insert(name.to_string(), team);
+        }
+        teams
+    }
+
+    #[test]
+    fn rank_changes_should_be_tracked() {
+        let mut leaderboard = Leaderboard::new(1);
+        leaderboard.record(1, &teams_with(&[("A", 0.9), ("B", 0.1)]));
+        leaderboard.record(2, &teams_with(&[("A", 0.2), ("B", 0.8)]));
+
+        let standings = leaderboard.standings();
+
+        assert_eq!(standings[0].team, "B");
+        assert_eq!(standings[0].change, 1);
+        assert_eq!(standings[1].team, "A");
+        assert_eq!(standings[1].change, -1);
+    }
+
+    #[test]
+    fn csv_export_should_quote_team_names() {
+        let mut leaderboard = Leaderboard::new(1);
+        leaderboard.record(1, &teams_with(&[("A, \"the\" team", 0.5)]));
+
+        let csv = leaderboard.to_csv();
+
+        assert_eq!(csv, "tick,team,rank,aggregate\n1,\"A, \"\"the\"\" team\",1,0.5\n");
+    }
+
+    #[test]
+    fn history_should_keep_the_latest_samples() {
+        let mut leaderboard = Leaderboard::new(1).history_length(2);
+        for tick in 1..=5 {
+            leaderboard.record(tick, &teams_with(&[("A", 0.5)]));
+        }
+
+        let ticks: Vec<u64> = leaderboard.history["A"]
+            .iter()
+            .map(|sample| sample.tick)
+            .collect();
+
+        assert_eq!(ticks, vec![4, 5]);
+    }
+}
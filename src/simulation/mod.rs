@@ -1,7 +1,23 @@
+pub mod arena;
+pub mod collision;
 pub mod communication;
+pub mod energy;
+pub mod event;
+pub mod flag;
+pub mod food;
+mod geometry;
+pub mod health;
+pub mod leaderboard;
+pub mod race;
+pub mod reproduction;
+pub mod score;
+mod snapshot;
+pub mod spatial;
+pub mod token;
+pub mod wind;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     convert::Into,
     f64::consts::PI,
     fmt::{Display, Error, Formatter},
@@ -12,18 +28,41 @@ use hyper::{self, Uri};
 use random::{self, Source, Value};
 use serde_json;
 
-use self::communication::Message;
+pub use self::snapshot::Snapshot;
+use self::{
+    arena::{Arena, Rule, World},
+    communication::{Message, Order},
+    event::Event,
+    health::{Health, Warning},
+    leaderboard::Leaderboard,
+    score::{Score, Scores},
+};
 use crate::{
-    brain::communication::Message as BrainMessage,
+    brain::{communication::Message as BrainMessage, Diagnostic},
     heartbeat::communication::Message as HeartbeatMessage,
+    persistence::communication::Message as PersistenceMessage,
     websocket::communication::Message as WsMessage,
 };
 
 const EPSILON: f64 = 0.01;
 
-const PHENOTYPE: Phenotype = Phenotype(0.1, 0.9, 0.01);
+pub const LEADERBOARD_INTERVAL: u64 = 30;
+
+const SPREAD: f64 = 0.02;
+
+const BRAIN_ERRORS: usize = 20;
+
+pub const POPULATION_CAP: usize = 100;
+
+/// The fastest a mutated phenotype may get.
+const MAX_SPEED: f64 = 0.05;
 
-struct Phenotype(f64, f64, f64);
+const PREY: Phenotype = Phenotype(0.1, 0.9, 0.01);
+
+const PREDATOR: Phenotype = Phenotype(0.05, 0.9, 0.012);
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Phenotype(f64, f64, f64);
 
 impl Phenotype {
     fn agility(&self) -> f64 {
@@ -36,11 +75,50 @@ impl Phenotype {
     fn max_speed(&self) -> f64 {
         self.2
     }
+
+    fn mutate<S>(&self, rate: f64, source: &mut S) -> Self
+    where
+        S: Source,
+    {
+        let mut mutated = |gene: f64| gene * (1f64 + rate * 2f64 * (source.read_f64() - 0.5));
+        Phenotype(
+            mutated(self.0).clamp(0f64, 1f64),
+            mutated(self.1).clamp(0f64, 1f64),
+            mutated(self.2).clamp(0f64, MAX_SPEED),
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum Role {
+    #[default]
+    Prey,
+    Predator,
+}
+
+impl Role {
+    pub fn phenotype(self) -> Phenotype {
+        match self {
+            Role::Prey => PREY,
+            Role::Predator => PREDATOR,
+        }
+    }
 }
 
-#[derive(Default)]
 pub struct Simulation {
     team_repository: Teams,
+    arena: Arena,
+    events: Vec<Event>,
+    persist_flocks: bool,
+    scores: Vec<Box<dyn Score + Send>>,
+    leaderboard: Leaderboard,
+    tick: u64,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub trait Simulate {
@@ -51,35 +129,102 @@ impl Simulation {
     pub fn new() -> Self {
         Self {
             team_repository: Teams::new(),
+            arena: Arena::new(),
+            events: Vec::new(),
+            persist_flocks: false,
+            scores: score::defaults(),
+            leaderboard: Leaderboard::new(LEADERBOARD_INTERVAL),
+            tick: 0,
+        }
+    }
+
+    pub fn restore(snapshot: Snapshot) -> Self {
+        Self {
+            team_repository: snapshot.into(),
+            arena: Arena::new(),
+            events: Vec::new(),
+            persist_flocks: false,
+            scores: score::defaults(),
+            leaderboard: Leaderboard::new(LEADERBOARD_INTERVAL),
+            tick: 0,
         }
     }
 
+    pub fn arena(mut self, arena: Arena) -> Self {
+        self.arena = arena;
+        self
+    }
+
+    pub fn persist_flocks(mut self, persist_flocks: bool) -> Self {
+        self.persist_flocks = persist_flocks;
+        self
+    }
+
+    pub fn score_with(mut self, score: Box<dyn Score + Send>) -> Self {
+        self.scores.push(score);
+        self
+    }
+
+    pub fn leaderboard(mut self, leaderboard: Leaderboard) -> Self {
+        self.leaderboard = leaderboard;
+        self
+    }
+
+    pub fn population_cap(mut self, population_cap: usize) -> Self {
+        self.team_repository.set_population_cap(population_cap);
+        self
+    }
+
     pub fn start(
         &mut self,
         rx: Receiver<Message>,
         brain_tx: Sender<BrainMessage>,
         heartbeat_tx: Sender<HeartbeatMessage>,
         ws_tx: Sender<WsMessage>,
+        persistence_tx: Sender<PersistenceMessage>,
     ) {
         loop {
+            let mut changed = false;
+            let mut updated = false;
             match rx.recv() {
                 Ok(message) => match message {
-                    Message::Register(registration) => {
+                    Message::Register(registration, reply_tx) => {
+                        let name = registration.name.clone();
                         let attempt = self.team_repository.register(registration);
-                        match attempt {
+                        let reply = match attempt {
                             RegistrationAttempt::Success => {
-                                info!("successfully registered a server")
+                                info!("successfully registered a server");
+                                changed = true;
+                                updated = true;
+                                Ok(self.team_repository.teams[&name].token.clone())
                             }
                             RegistrationAttempt::Failure(reason) => {
-                                error!("problem registering a server: \"{:?}\"", reason)
+                                error!("problem registering a server: \"{:?}\"", reason);
+                                Err(reason.into())
                             }
+                        };
+                        if reply_tx.send(reply).is_err() {
+                            error!("could not reply to registration of {}", name);
+                        }
+                    }
+                    Message::Authenticate(name, token, reply_tx) => {
+                        let authenticated = self
+                            .team_repository
+                            .teams
+                            .get(&name)
+                            .map(|team| token::matches(&team.token, &token))
+                            .unwrap_or(false);
+                        if reply_tx.send(authenticated).is_err() {
+                            error!("could not reply to authentication of {}", name);
                         }
                     }
                     Message::Unregister(unregistration) => {
                         let attempt = self.team_repository.unregister(unregistration);
                         match attempt {
                             UnregistrationAttempt::Success => {
-                                info!("successfully unregistered a server")
+                                info!("successfully unregistered a server");
+                                changed = true;
+                                updated = true;
                             }
                             UnregistrationAttempt::Failure(reason) => {
                                 error!("problem unregistering a server: \"{:?}\"", reason)
@@ -100,42 +245,214 @@ impl Simulation {
                     }
                     Message::HeartbeatStatus((name, connected)) => {
                         match self.team_repository.teams.get_mut(&name) {
-                            Some(team) => team.set_connection_status(connected),
+                            Some(team) => {
+                                team.set_connection_status(connected);
+                                updated = true;
+                            }
                             None => {
                                 info!("received heartbeat status for {} while unregistered", name)
                             }
                         }
                     }
                     Message::Tick => {
+                        self.tick += 1;
                         self.step(1f64);
+                        self.team_repository.score(&self.scores);
+                        self.leaderboard.record(self.tick, &self.team_repository);
                         self.control(brain_tx.clone());
+                        self.team_repository.clear_warnings();
+                        changed = self.persist_flocks;
+                        updated = true;
                     }
                     Message::SpawnAll(n) => {
                         info!("spawning {} boids in all connected teams", n);
                         self.team_repository.spawn(n);
+                        changed = self.persist_flocks;
+                        updated = true;
                     }
                     Message::Spawn((team_name, n)) => {
                         info!("spawning {} boids in team {}", n, team_name);
                         self.team_repository.spawn_in_team(team_name, n);
+                        changed = self.persist_flocks;
+                        updated = true;
                     }
                     Message::BrainUpdate(team_name, intentions) => {
                         info!("processing brain update for {}", team_name);
                         self.team_repository.update(team_name, &intentions);
                     }
+                    Message::BrainError(team_name, diagnostic) => {
+                        if let Some(team) = self.team_repository.teams.get_mut(&team_name) {
+                            if team.record_brain_error(diagnostic.clone()) {
+                                self.events.push(Event::BrainError {
+                                    team: team_name,
+                                    diagnostic,
+                                });
+                            }
+                        }
+                    }
+                    Message::BrainErrors(team_name, reply_tx) => {
+                        let errors = self
+                            .team_repository
+                            .teams
+                            .get(&team_name)
+                            .map(|team| team.brain_errors.iter().cloned().collect());
+                        if reply_tx.send(errors).is_err() {
+                            error!("could not reply with brain errors of {}", team_name);
+                        }
+                    }
+                    Message::Scores(reply_tx) => {
+                        if reply_tx.send(self.team_repository.scores()).is_err() {
+                            error!("could not reply with scores");
+                        }
+                    }
+                    Message::Order(order, reply_tx) => {
+                        info!("executing {:?}", order);
+                        let assigned = matches!(order, Order::Assign { .. });
+                        let result = self.order(order);
+                        changed = result.is_ok() && (assigned || self.persist_flocks);
+                        updated = result.is_ok();
+                        if reply_tx.send(result).is_err() {
+                            error!("could not reply to order");
+                        }
+                    }
+                    Message::Health(name, reply_tx) => {
+                        if reply_tx.send(self.team_repository.health(&name)).is_err() {
+                            error!("could not reply with health of {}", name);
+                        }
+                    }
+                    Message::Standings(reply_tx) => {
+                        if reply_tx
+                            .send(self.leaderboard.standings().to_vec())
+                            .is_err()
+                        {
+                            error!("could not reply with standings");
+                        }
+                    }
+                    Message::Leaderboard(reply_tx) => {
+                        if reply_tx.send(self.leaderboard.clone()).is_err() {
+                            error!("could not reply with leaderboard");
+                        }
+                    }
+                    Message::State(reply_tx) => {
+                        if reply_tx.send(self.state()).is_err() {
+                            error!("could not reply with state");
+                        }
+                    }
+                    Message::Stop => {
+                        info!("stopping simulation at tick {}", self.tick);
+                        self.persist(&persistence_tx);
+                        return;
+                    }
                 },
 
                 Err(error) => {
                     error!("could not receive message: {}", error);
+                    return;
+                }
+            }
+
+            if changed {
+                self.persist(&persistence_tx);
+            }
+
+            if !self.events.is_empty() {
+                let events: Vec<Event> = self.events.drain(..).collect();
+                if let Ok(json) = serde_json::to_value(&Events { events }) {
+                    if let Err(error) = ws_tx.send(WsMessage::Events(json)) {
+                        error!("could not send events message: {}", error);
+                    }
+                } else {
+                    error!("could not serialize events");
                 }
             }
 
-            if let Ok(json) = serde_json::to_string(&self.team_repository) {
-                if let Err(error) = ws_tx.send(WsMessage::Update(json)) {
-                    error!("could not send update message: {}", error);
+            if updated {
+                self.broadcast(&ws_tx);
+            }
+        }
+    }
+
+    fn broadcast(&self, ws_tx: &Sender<WsMessage>) {
+        let update = Update {
+            teams: &self.team_repository,
+            world: self.arena.world(),
+        };
+        if let Ok(json) = serde_json::to_value(&update) {
+            if let Err(error) = ws_tx.send(WsMessage::Update(json)) {
+                error!("could not send update message: {}", error);
+            }
+        } else {
+            error!("could not serialize team_repository");
+        }
+    }
+
+    fn state(&self) -> serde_json::Value {
+        let update = Update {
+            teams: &self.team_repository,
+            world: self.arena.world(),
+        };
+        let mut state = serde_json::to_value(&update).unwrap_or_default();
+        if let serde_json::Value::Object(fields) = &mut state {
+            fields.insert("tick".to_string(), self.tick.into());
+        }
+        state
+    }
+
+    fn order(&mut self, order: Order) -> Result<(), String> {
+        match order {
+            Order::Spawn {
+                team,
+                count,
+                position,
+            } => {
+                let team = self.team_mut(&team)?;
+                match position {
+                    Some((x, y)) => team.flock.spawn_near(x, y, count),
+                    None => team.spawn(count),
+                }
+            }
+            Order::SpawnAll { count } => self.team_repository.spawn(count),
+            Order::Kill { team, boids } => {
+                let team = self.team_mut(&team)?;
+                let unknown: Vec<String> = boids
+                    .iter()
+                    .filter(|id| !team.flock.boids.contains_key(id))
+                    .map(|id| id.0.to_string())
+                    .collect();
+                if !unknown.is_empty() {
+                    return Err(format!("unknown boids {}", unknown.join(", ")));
+                }
+                boids.iter().for_each(|id| {
+                    team.flock.boids.remove(id);
+                });
+            }
+            Order::Clear { team } => self.team_mut(&team)?.flock.boids.clear(),
+            Order::Toggle { rule, enabled } => {
+                self.arena.toggle(rule, enabled)?;
+                if rule == Rule::Wind && !enabled {
+                    self.team_repository.clear_drift();
                 }
-            } else {
-                error!("could not serialize team_repository");
             }
+            Order::Assign { team, role } => self.team_mut(&team)?.role = role,
+        }
+        Ok(())
+    }
+
+    fn team_mut(&mut self, name: &str) -> Result<&mut Team, String> {
+        self.team_repository
+            .teams
+            .get_mut(name)
+            .ok_or_else(|| format!("unknown team \"{}\"", name))
+    }
+
+    fn persist(&self, tx: &Sender<PersistenceMessage>) {
+        let snapshot = self.team_repository.snapshot(self.persist_flocks);
+        if let Ok(json) = serde_json::to_string(&snapshot) {
+            if let Err(error) = tx.send(PersistenceMessage::Snapshot(json)) {
+                error!("could not send snapshot message: {}", error);
+            }
+        } else {
+            error!("could not serialize snapshot");
         }
     }
 
@@ -146,7 +463,13 @@ impl Simulation {
             .iter()
             .filter(|(_, team)| team.connected)
             .filter(|(_, team)| !team.flock.is_empty())
-            .map(|(name, team)| (name, team.brain_uri(), team.brain_payload()))
+            .map(|(name, team)| {
+                (
+                    name,
+                    team.brain_uri(),
+                    team.brain_payload(self.arena.world()),
+                )
+            })
             .filter(|(_, uri, _)| uri.is_ok())
             .filter(|(_, _, payload)| payload.is_ok())
             .map(|(name, uri, payload)| {
@@ -166,9 +489,24 @@ impl Simulation {
 impl Simulate for Simulation {
     fn step(&mut self, dt: f64) {
         self.team_repository.step(dt);
+        let events = self.arena.step(&mut self.team_repository, self.tick);
+        self.events.extend(events);
     }
 }
 
+#[derive(Serialize)]
+struct Update<'a> {
+    #[serde(flatten)]
+    teams: &'a Teams,
+    #[serde(flatten)]
+    world: World<'a>,
+}
+
+#[derive(Serialize)]
+struct Events {
+    events: Vec<Event>,
+}
+
 pub trait Spawn {
     fn spawn(&mut self, n: usize);
 }
@@ -188,8 +526,9 @@ impl TeamRepository for Teams {
             return RegistrationAttempt::Failure(RegistrationFailureReason::IPAddressWithPortTaken);
         }
 
-        self.teams
-            .insert(registration.name.clone(), registration.into());
+        let mut team: Team = registration.into();
+        team.flock.set_population_cap(self.population_cap);
+        self.teams.insert(team.name.clone(), team);
         RegistrationAttempt::Success
     }
 
@@ -233,15 +572,30 @@ pub struct Registration {
     name: String,
     ip_address: String,
     port: u16,
+    #[serde(default)]
+    control: Control,
 }
 
 impl From<Registration> for Team {
     fn from(registratration: Registration) -> Self {
-        Team::new(
+        let mut team = Team::new(
             registratration.name,
             registratration.ip_address,
             registratration.port,
-        )
+        );
+        team.control = registratration.control;
+        team
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegistrationSuccess {
+    token: String,
+}
+
+impl RegistrationSuccess {
+    pub fn new(token: String) -> RegistrationSuccess {
+        RegistrationSuccess { token }
     }
 }
 
@@ -293,18 +647,34 @@ impl UnregistrationFailure {
     }
 }
 
-#[derive(Serialize, Default)]
+#[derive(Serialize)]
 pub struct Teams {
     pub teams: HashMap<String, Team>,
+    #[serde(skip)]
+    population_cap: usize,
+}
+
+impl Default for Teams {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Teams {
     pub fn new() -> Teams {
         Teams {
             teams: HashMap::new(),
+            population_cap: POPULATION_CAP,
         }
     }
 
+    pub fn set_population_cap(&mut self, population_cap: usize) {
+        self.population_cap = population_cap;
+        self.teams
+            .iter_mut()
+            .for_each(|(_, team)| team.flock.set_population_cap(population_cap))
+    }
+
     pub fn available(&self, ip_address: &str, port: u16) -> bool {
         self.teams
             .iter()
@@ -326,6 +696,43 @@ impl Teams {
             .iter_mut()
             .for_each(|team| team.update(intentions))
     }
+
+    pub fn score(&mut self, scores: &[Box<dyn Score + Send>]) {
+        self.teams
+            .iter_mut()
+            .for_each(|(_, team)| team.scores = Scores::evaluate(scores, team))
+    }
+
+    pub fn clear_drift(&mut self) {
+        self.teams.iter_mut().for_each(|(_, team)| {
+            team.flock
+                .boids
+                .values_mut()
+                .for_each(|boid| boid.drift = None)
+        })
+    }
+
+    pub fn clear_warnings(&mut self) {
+        self.teams
+            .iter_mut()
+            .for_each(|(_, team)| team.warnings.clear())
+    }
+
+    pub fn health(&self, name: &str) -> Option<Health> {
+        self.teams.get(name).map(|team| Health {
+            name: team.name.clone(),
+            connected: team.connected,
+            boids: team.flock.boids.len(),
+            warnings: team.last_warnings.clone(),
+        })
+    }
+
+    pub fn scores(&self) -> HashMap<String, Scores> {
+        self.teams
+            .iter()
+            .map(|(name, team)| (name.clone(), team.scores.clone()))
+            .collect()
+    }
 }
 
 impl Simulate for Teams {
@@ -346,7 +753,27 @@ pub struct Team {
     ip_address: String,
     port: u16,
     connected: bool,
+    role: Role,
+    control: Control,
     flock: Flock,
+    captures: u64,
+    food: u64,
+    flags: u64,
+    scores: Scores,
+    #[serde(skip)]
+    age: u64,
+    #[serde(skip)]
+    alive: u64,
+    #[serde(skip)]
+    warnings: Vec<Warning>,
+    #[serde(skip)]
+    last_warnings: Vec<Warning>,
+    #[serde(skip)]
+    brain_errors: VecDeque<Diagnostic>,
+    #[serde(skip)]
+    failing: bool,
+    #[serde(skip)]
+    token: String,
 }
 
 impl Team {
@@ -360,7 +787,20 @@ impl Team {
             ip_address: ip_address.into(),
             port,
             connected: false,
+            role: Role::default(),
+            control: Control::default(),
             flock,
+            captures: 0,
+            food: 0,
+            flags: 0,
+            scores: Scores::default(),
+            age: 0,
+            alive: 0,
+            warnings: Vec::new(),
+            last_warnings: Vec::new(),
+            brain_errors: VecDeque::with_capacity(BRAIN_ERRORS),
+            failing: false,
+            token: token::generate(),
         }
     }
 
@@ -376,8 +816,28 @@ impl Team {
         address.parse()
     }
 
-    pub fn brain_payload(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(&self.flock)
+    pub fn brain_payload(&self, world: World) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&Payload {
+            flock: &self.flock,
+            warnings: &self.warnings,
+            world,
+        })
+    }
+
+    /// Keeps the diagnostic and tells whether the brain fails differently than
+    /// it did on the previous request.
+    pub fn record_brain_error(&mut self, diagnostic: Diagnostic) -> bool {
+        let changed = !self.failing
+            || self
+                .brain_errors
+                .back()
+                .is_none_or(|last| !last.same_failure(&diagnostic));
+        self.failing = true;
+        if self.brain_errors.len() == BRAIN_ERRORS {
+            self.brain_errors.pop_front();
+        }
+        self.brain_errors.push_back(diagnostic);
+        changed
     }
 
     pub fn set_connection_status(&mut self, connected: bool) {
@@ -385,13 +845,39 @@ impl Team {
     }
 
     pub fn update(&mut self, intentions: &Intentions) {
-        self.flock.update(intentions);
+        let warnings = self
+            .flock
+            .update(intentions, self.control, &self.role.phenotype());
+        self.failing = false;
+        if !warnings.is_empty() {
+            warn!(
+                "{} warnings for intentions of {}",
+                warnings.len(),
+                self.name
+            );
+        }
+        self.warnings.extend(warnings.iter().cloned());
+        self.last_warnings = warnings;
     }
 }
 
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    flock: &'a Flock,
+    #[serde(skip_serializing_if = "<[Warning]>::is_empty")]
+    warnings: &'a [Warning],
+    #[serde(flatten)]
+    world: World<'a>,
+}
+
 impl Simulate for Team {
     fn step(&mut self, dt: f64) {
-        self.flock.step(dt);
+        self.flock.step(dt, &self.role.phenotype());
+        self.age += 1;
+        if !self.flock.is_empty() {
+            self.alive += 1;
+        }
     }
 }
 
@@ -407,41 +893,118 @@ impl Display for Team {
     }
 }
 
-#[derive(Serialize, Default)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Flock {
     pub boids: HashMap<FlockId, Boid>,
+    #[serde(skip, default = "population_cap")]
+    population_cap: usize,
+}
+
+fn population_cap() -> usize {
+    POPULATION_CAP
+}
+
+impl Default for Flock {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Flock {
     pub fn new() -> Flock {
         let boids = HashMap::new();
-        Flock { boids }
+        Flock {
+            boids,
+            population_cap: POPULATION_CAP,
+        }
+    }
+
+    pub fn set_population_cap(&mut self, population_cap: usize) {
+        self.population_cap = population_cap;
     }
 
     pub fn is_empty(&self) -> bool {
         self.boids.is_empty()
     }
 
-    pub fn update(&mut self, intentions: &Intentions) {
-        self.boids.iter_mut().for_each(|(name, boid)| {
-            if intentions.0.contains_key(name) {
-                let intent =
-                    intentions.0.get(name).unwrap(/* safe because of the contains_key check */);
-                boid.update(*intent);
+    pub fn is_full(&self) -> bool {
+        self.boids.len() >= self.population_cap
+    }
+
+    /// How many boids can be spawned before the flock is full.
+    fn room(&self, n: usize) -> usize {
+        n.min(self.population_cap.saturating_sub(self.boids.len()))
+    }
+
+    pub fn spawn_near(&mut self, x: f64, y: f64, n: usize) {
+        let mut source = random::default();
+        let n = self.room(n);
+        let old_size = self.boids.len();
+        while (self.boids.len() - old_size) < n {
+            let identifier = source.read::<FlockId>();
+            let mut boid = source.read::<Boid>();
+            boid.x = x + SPREAD * (source.read_f64() - 0.5);
+            boid.y = y + SPREAD * (source.read_f64() - 0.5);
+            boid.normalize(&PREY);
+            self.boids.insert(identifier, boid);
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        intentions: &Intentions,
+        control: Control,
+        phenotype: &Phenotype,
+    ) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut ids: Vec<&FlockId> = intentions.0.keys().collect();
+        ids.sort_by_key(|id| id.0);
+        for id in ids {
+            let intent = intentions.0[id];
+            let boid = match self.boids.get_mut(id) {
+                Some(boid) => boid,
+                None => {
+                    warnings.push(Warning::UnknownBoid { boid: *id });
+                    continue;
+                }
+            };
+            if intent.control() != control {
+                warnings.push(Warning::WrongControl { boid: *id });
+                continue;
             }
-        });
+            if !intent.is_finite() {
+                warnings.push(Warning::NotFinite { boid: *id });
+                continue;
+            }
+            let clamped = intent.clamped(&boid.phenotype.unwrap_or(*phenotype));
+            if clamped != intent {
+                warnings.push(Warning::Clamped { boid: *id });
+            }
+            boid.update(clamped);
+        }
+
+        let missing = self
+            .boids
+            .keys()
+            .filter(|id| !intentions.0.contains_key(id))
+            .count();
+        if missing > 0 {
+            warnings.push(Warning::Missing { boids: missing });
+        }
+        warnings
     }
-}
 
-impl Simulate for Flock {
-    fn step(&mut self, dt: f64) {
-        self.boids.iter_mut().for_each(|(_, boid)| boid.step(dt))
+    fn step(&mut self, dt: f64, phenotype: &Phenotype) {
+        self.boids
+            .iter_mut()
+            .for_each(|(_, boid)| boid.step(dt, phenotype))
     }
 }
 
 impl Spawn for Flock {
     fn spawn(&mut self, n: usize) {
         let mut source = random::default();
+        let n = self.room(n);
         let old_size = self.boids.len();
         while (self.boids.len() - old_size) < n {
             let identifier = source.read::<FlockId>();
@@ -451,7 +1014,7 @@ impl Spawn for Flock {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Hash, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct FlockId(u64);
 
 impl From<u64> for FlockId {
@@ -471,13 +1034,39 @@ impl Value for FlockId {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Boid {
     x: f64,
     y: f64,
     heading: f64,
     speed: f64,
     intent: Option<Intent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    carrying: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    stunned: u32,
+    #[serde(default = "full")]
+    energy: f64,
+    #[serde(default)]
+    age: u64,
+    #[serde(skip)]
+    turned: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phenotype: Option<Phenotype>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    drift: Option<(f64, f64)>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn full() -> f64 {
+    1f64
+}
+
+fn wrapped(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2f64 * PI) - PI
 }
 
 impl Boid {
@@ -488,6 +1077,13 @@ impl Boid {
             heading,
             speed,
             intent: None,
+            carrying: None,
+            stunned: 0,
+            energy: full(),
+            age: 0,
+            turned: 0f64,
+            phenotype: None,
+            drift: None,
         }
     }
 
@@ -495,62 +1091,90 @@ impl Boid {
         self.intent = Some(intent)
     }
 
-    fn follow_intent(&mut self) {
-        let agility = PHENOTYPE.agility();
-        let acceleration = PHENOTYPE.acceleration();
-        let mut heading_epsilon = 0f64;
-        let mut speed_epsilon = 0f64;
-        if let Some(intent) = self.intent {
-            let mut delta = intent.heading - self.heading;
-            while delta < -PI {
-                delta += 2f64 * PI;
-            }
-            while delta > PI {
-                delta -= 2f64 * PI;
-            }
-            delta *= agility;
-            self.heading += delta;
-            self.speed = (1f64 - acceleration) * self.speed + acceleration * intent.speed;
-
-            heading_epsilon = (self.heading - intent.heading).abs();
-            speed_epsilon = (self.speed - intent.speed).abs();
+    fn follow_intent(&mut self, phenotype: &Phenotype) {
+        self.turned = 0f64;
+        match self.intent {
+            Some(Intent::Target { heading, speed }) => self.approach(heading, speed, phenotype),
+            Some(Intent::Steering { ax, ay }) => self.steer(ax, ay, phenotype),
+            Some(Intent::Thrust { turn, thrust }) => self.thrust(turn, thrust, phenotype),
+            None => {}
         }
+    }
+
+    fn approach(&mut self, heading: f64, speed: f64, phenotype: &Phenotype) {
+        let agility = phenotype.agility();
+        let acceleration = phenotype.acceleration();
+        let delta = wrapped(heading - self.heading) * agility;
+        self.heading += delta;
+        self.turned = delta.abs();
+        self.speed = (1f64 - acceleration) * self.speed + acceleration * speed;
+
+        let heading_epsilon = (self.heading - heading).abs();
+        let speed_epsilon = (self.speed - speed).abs();
         if heading_epsilon < EPSILON && speed_epsilon < EPSILON {
             self.intent = None
         }
     }
 
-    fn normalize(&mut self) {
-        while self.x < 0f64 {
-            self.x += 1f64;
-        }
-        while self.x > 1f64 {
-            self.x -= 1f64;
-        }
-        while self.y < 0f64 {
-            self.y += 1f64;
-        }
-        while self.y > 1f64 {
-            self.y -= 1f64;
-        }
-        while self.heading < -PI {
-            self.heading += 2f64 * PI;
-        }
-        while self.heading > PI {
-            self.heading -= 2f64 * PI;
-        }
-        if self.speed < 0.0 {
-            self.speed = 0.0;
+    fn steer(&mut self, ax: f64, ay: f64, phenotype: &Phenotype) {
+        let max_force = phenotype.acceleration() * phenotype.max_speed();
+        let magnitude = (ax * ax + ay * ay).sqrt();
+        let scale = if magnitude > max_force {
+            max_force / magnitude
+        } else {
+            1f64
+        };
+        let vx = self.speed * self.heading.cos() + scale * ax;
+        let vy = self.speed * self.heading.sin() + scale * ay;
+        if vx != 0f64 || vy != 0f64 {
+            self.turn(wrapped(vy.atan2(vx) - self.heading), phenotype);
         }
-        if self.speed > PHENOTYPE.max_speed() {
-            self.speed = PHENOTYPE.max_speed();
+        self.speed = (vx * vx + vy * vy).sqrt();
+    }
+
+    fn thrust(&mut self, turn: f64, thrust: f64, phenotype: &Phenotype) {
+        let max_force = phenotype.acceleration() * phenotype.max_speed();
+        self.turn(turn, phenotype);
+        self.speed += thrust.clamp(-max_force, max_force);
+    }
+
+    fn turn(&mut self, turn: f64, phenotype: &Phenotype) {
+        let max_turn = phenotype.agility() * PI;
+        let turn = turn.clamp(-max_turn, max_turn);
+        self.heading += turn;
+        self.turned = turn.abs();
+    }
+
+    fn normalize(&mut self, phenotype: &Phenotype) {
+        for value in [&mut self.x, &mut self.y, &mut self.heading, &mut self.speed] {
+            if !value.is_finite() {
+                *value = 0f64;
+            }
         }
+        self.x = self.x.rem_euclid(1f64);
+        self.y = self.y.rem_euclid(1f64);
+        self.heading = wrapped(self.heading);
+        self.speed = self.speed.clamp(0f64, phenotype.max_speed());
     }
-}
 
-impl Simulate for Boid {
-    fn step(&mut self, dt: f64) {
-        self.follow_intent();
+    fn push(&mut self, dx: f64, dy: f64, dvx: f64, dvy: f64) {
+        let vx = self.speed * self.heading.cos() + dvx;
+        let vy = self.speed * self.heading.sin() + dvy;
+        self.x = (self.x + dx).rem_euclid(1f64);
+        self.y = (self.y + dy).rem_euclid(1f64);
+        self.heading = vy.atan2(vx);
+        self.speed = (vx * vx + vy * vy).sqrt();
+    }
+
+    fn step(&mut self, dt: f64, phenotype: &Phenotype) {
+        let phenotype = &self.phenotype.unwrap_or(*phenotype);
+        self.age += 1;
+        if self.stunned > 0 {
+            self.turned = 0f64;
+            self.stunned -= 1;
+            return;
+        }
+        self.follow_intent(phenotype);
 
         let d = self.speed * dt;
         let dx = d * self.heading.cos();
@@ -559,7 +1183,7 @@ impl Simulate for Boid {
         self.x += dx;
         self.y += dy;
 
-        self.normalize()
+        self.normalize(phenotype)
     }
 }
 
@@ -579,10 +1203,64 @@ impl Value for Boid {
 #[derive(Deserialize, Debug)]
 pub struct Intentions(HashMap<FlockId, Intent>);
 
-#[derive(Deserialize, Serialize, Debug, Copy, Clone)]
-pub struct Intent {
-    pub heading: f64,
-    pub speed: f64,
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Intent {
+    Target { heading: f64, speed: f64 },
+    Steering { ax: f64, ay: f64 },
+    Thrust { turn: f64, thrust: f64 },
+}
+
+impl Intent {
+    pub fn control(&self) -> Control {
+        match self {
+            Intent::Target { .. } => Control::Target,
+            Intent::Steering { .. } => Control::Steering,
+            Intent::Thrust { .. } => Control::Thrust,
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        match *self {
+            Intent::Target { heading, speed } => heading.is_finite() && speed.is_finite(),
+            Intent::Steering { ax, ay } => ax.is_finite() && ay.is_finite(),
+            Intent::Thrust { turn, thrust } => turn.is_finite() && thrust.is_finite(),
+        }
+    }
+
+    fn clamped(self, phenotype: &Phenotype) -> Self {
+        let max_force = phenotype.acceleration() * phenotype.max_speed();
+        let max_turn = phenotype.agility() * PI;
+        match self {
+            Intent::Target { heading, speed } => Intent::Target {
+                heading,
+                speed: speed.clamp(0f64, phenotype.max_speed()),
+            },
+            Intent::Steering { ax, ay } => {
+                let magnitude = (ax * ax + ay * ay).sqrt();
+                if magnitude > max_force {
+                    Intent::Steering {
+                        ax: ax * max_force / magnitude,
+                        ay: ay * max_force / magnitude,
+                    }
+                } else {
+                    self
+                }
+            }
+            Intent::Thrust { turn, thrust } => Intent::Thrust {
+                turn: turn.clamp(-max_turn, max_turn),
+                thrust: thrust.clamp(-max_force, max_force),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum Control {
+    #[default]
+    Target,
+    Steering,
+    Thrust,
 }
 
 #[cfg(test)]
@@ -596,6 +1274,7 @@ mod tests {
             name: "TEST".to_owned(),
             ip_address: "TEST ADDRESS".to_owned(),
             port: 2643,
+            control: Control::Target,
         };
 
         let result = teams.register(registration);
@@ -610,6 +1289,7 @@ mod tests {
             name: "TEST".to_owned(),
             ip_address: "TEST ADDRESS".to_owned(),
             port: 2643,
+            control: Control::Target,
         };
         let _ = teams.register(first);
 
@@ -617,6 +1297,7 @@ mod tests {
             name: "TEST".to_owned(),
             ip_address: "OTHER TEST ADDRESS".to_owned(),
             port: 2643,
+            control: Control::Target,
         };
         let result = teams.register(second);
 
@@ -633,6 +1314,7 @@ mod tests {
             name: "TEST".to_owned(),
             ip_address: "TEST ADDRESS".to_owned(),
             port: 2643,
+            control: Control::Target,
         };
         let _ = teams.register(first);
 
@@ -640,6 +1322,7 @@ mod tests {
             name: "OTHER TEST".to_owned(),
             ip_address: "TEST ADDRESS".to_owned(),
             port: 2643,
+            control: Control::Target,
         };
         let result = teams.register(second);
 
@@ -648,4 +1331,213 @@ mod tests {
             RegistrationAttempt::Failure(RegistrationFailureReason::IPAddressWithPortTaken)
         );
     }
+
+    #[test]
+    fn registered_teams_should_be_prey() {
+        let mut teams = Teams::new();
+        let registration = serde_json::from_str(
+            r#"{"name": "TEST", "ip_address": "TEST ADDRESS", "port": 2643, "role": "Predator"}"#,
+        )
+        .unwrap();
+
+        let _ = teams.register(registration);
+
+        assert_eq!(teams.teams["TEST"].role, Role::Prey);
+    }
+
+    #[test]
+    fn restored_teams_should_be_disconnected() {
+        let mut teams = Teams::new();
+        let registration = Registration {
+            name: "TEST".to_owned(),
+            ip_address: "TEST ADDRESS".to_owned(),
+            port: 2643,
+            control: Control::Target,
+        };
+        let _ = teams.register(registration);
+        teams
+            .teams
+            .get_mut("TEST")
+            .unwrap()
+            .set_connection_status(true);
+        teams.spawn(3);
+
+        let json = serde_json::to_string(&teams.snapshot(true)).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        let restored: Teams = snapshot.into();
+
+        let team = restored.teams.get("TEST").unwrap();
+        assert!(!team.connected);
+        assert_eq!(team.token, teams.teams["TEST"].token);
+        assert_eq!(team.flock.boids.len(), 3);
+        assert!(!restored.available("TEST ADDRESS", 2643));
+    }
+
+    #[test]
+    fn spawning_should_stop_at_the_population_cap() {
+        let mut teams = Teams::new();
+        teams
+            .teams
+            .insert("TEST".to_owned(), Team::new("TEST", "TEST ADDRESS", 2643));
+        teams.set_population_cap(5);
+
+        teams.spawn(3);
+        teams.spawn_in_team("TEST".to_owned(), 1_000_000);
+        teams
+            .teams
+            .get_mut("TEST")
+            .unwrap()
+            .flock
+            .spawn_near(0.5, 0.5, 10);
+
+        assert_eq!(teams.teams["TEST"].flock.boids.len(), 5);
+    }
+
+    #[test]
+    fn turning_the_wind_off_should_clear_the_drift() {
+        let mut arena = Arena::new();
+        arena.wind = Some("uniform:0.01,0".parse().unwrap());
+        let mut simulation = Simulation::new().arena(arena);
+        simulation
+            .team_repository
+            .teams
+            .insert("TEST".to_owned(), Team::new("TEST", "TEST ADDRESS", 2643));
+        simulation.team_repository.spawn(3);
+        simulation.step(0.1);
+
+        let order = Order::Toggle {
+            rule: Rule::Wind,
+            enabled: false,
+        };
+        simulation.order(order).unwrap();
+
+        let boids = &simulation.team_repository.teams["TEST"].flock.boids;
+        assert!(boids.values().all(|boid| boid.drift.is_none()));
+    }
+
+    #[test]
+    fn killing_unknown_boids_should_be_an_error() {
+        let mut simulation = Simulation::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(0.5, 0.5, 0.0, 0.0));
+        simulation
+            .team_repository
+            .teams
+            .insert("TEST".to_owned(), team);
+        let kill = |boids: Vec<u64>| Order::Kill {
+            team: "TEST".to_owned(),
+            boids: boids.into_iter().map(FlockId::from).collect(),
+        };
+
+        let result = simulation.order(kill(vec![1, 7, 8]));
+
+        assert_eq!(result, Err("unknown boids 7, 8".to_owned()));
+        let boids =
+            |simulation: &Simulation| simulation.team_repository.teams["TEST"].flock.boids.len();
+        assert_eq!(boids(&simulation), 1);
+        assert!(simulation.order(kill(vec![1])).is_ok());
+        assert_eq!(boids(&simulation), 0);
+    }
+
+    #[test]
+    fn mutation_should_not_exceed_the_maximum_speed() {
+        let mut source = random::default();
+        let mut phenotype = PREY;
+        for _ in 0..1000 {
+            phenotype = phenotype.mutate(1f64, &mut source);
+        }
+
+        assert!(phenotype.max_speed() <= MAX_SPEED);
+    }
+
+    #[test]
+    fn brain_errors_should_only_be_announced_when_they_change() {
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        let diagnostic = |status, latency| Diagnostic {
+            error: crate::brain::Error::Status,
+            status: Some(status),
+            excerpt: None,
+            latency,
+        };
+
+        assert!(team.record_brain_error(diagnostic(500, 10)));
+        assert!(!team.record_brain_error(diagnostic(500, 20)));
+        assert!(team.record_brain_error(diagnostic(503, 20)));
+        team.update(&Intentions(HashMap::new()));
+        assert!(team.record_brain_error(diagnostic(503, 20)));
+        assert_eq!(team.brain_errors.len(), 4);
+    }
+
+    #[test]
+    fn thrust_should_be_limited_by_the_phenotype() {
+        let mut boid = Boid::new(0.5, 0.5, 0.0, 0.0);
+        boid.update(Intent::Thrust {
+            turn: PI,
+            thrust: 1.0,
+        });
+
+        boid.step(1f64, &PREY);
+
+        assert!((boid.heading - PREY.agility() * PI).abs() < 1e-9);
+        assert!((boid.speed - PREY.acceleration() * PREY.max_speed()).abs() < 1e-9);
+        assert!(boid.intent.is_some());
+    }
+
+    #[test]
+    fn malformed_intentions_should_be_reported() {
+        let mut flock = Flock::new();
+        flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(0.5, 0.5, 0.0, 0.0));
+        flock
+            .boids
+            .insert(FlockId::from(2), Boid::new(0.5, 0.5, 0.0, 0.0));
+        let intentions: Intentions = serde_json::from_str(
+            r#"{"1": {"heading": 0.0, "speed": 1.0}, "3": {"heading": 0.0, "speed": 0.0}}"#,
+        )
+        .unwrap();
+
+        let warnings = flock.update(&intentions, Control::Target, &PREY);
+
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::Clamped {
+                    boid: FlockId::from(1)
+                },
+                Warning::UnknownBoid {
+                    boid: FlockId::from(3)
+                },
+                Warning::Missing { boids: 1 },
+            ]
+        );
+
+        let mut intentions = Intentions(HashMap::new());
+        intentions.0.insert(
+            FlockId::from(1),
+            Intent::Target {
+                heading: f64::INFINITY,
+                speed: f64::NAN,
+            },
+        );
+
+        let warnings = flock.update(&intentions, Control::Target, &PREY);
+
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::NotFinite {
+                    boid: FlockId::from(1)
+                },
+                Warning::Missing { boids: 1 },
+            ]
+        );
+        let boid = flock.boids.get_mut(&FlockId::from(1)).unwrap();
+        assert!(boid.intent.is_none_or(|intent| intent.is_finite()));
+        boid.step(1f64, &PREY);
+        assert!(boid.x.is_finite() && boid.speed.is_finite());
+        assert!(boid.heading.is_finite());
+    }
 }
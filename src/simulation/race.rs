@@ -0,0 +1,184 @@
+use std::{collections::HashMap, str::FromStr};
+
+use super::{
+    event::Event,
+    geometry::{circular_mean, distance},
+    FlockId, Teams,
+};
+
+pub struct Race {
+    checkpoints: Vec<Checkpoint>,
+    tracking: Tracking,
+    finishers: usize,
+    progress: HashMap<String, Progress>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Checkpoint {
+    x: f64,
+    y: f64,
+    radius: f64,
+}
+
+impl Checkpoint {
+    pub fn new(x: f64, y: f64, radius: f64) -> Self {
+        Self { x, y, radius }
+    }
+
+    fn reached_by(&self, x: f64, y: f64) -> bool {
+        distance(self.x, self.y, x, y) <= self.radius
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tracking {
+    Boids,
+    Centroid,
+}
+
+impl FromStr for Tracking {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "boids" => Ok(Tracking::Boids),
+            "centroid" => Ok(Tracking::Centroid),
+            _ => Err(format!("unknown race tracking \"{}\"", source)),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Progress {
+    checkpoints: usize,
+    finished: Option<u64>,
+    #[serde(skip)]
+    boids: HashMap<FlockId, usize>,
+}
+
+impl Race {
+    pub fn new(checkpoints: Vec<Checkpoint>, tracking: Tracking, finishers: usize) -> Self {
+        Self {
+            checkpoints,
+            tracking,
+            finishers,
+            progress: HashMap::new(),
+        }
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn progress(&self) -> &HashMap<String, Progress> {
+        &self.progress
+    }
+
+    pub fn step(&mut self, teams: &Teams, tick: u64) -> Vec<Event> {
+        // Finished teams keep their result after they unregister.
+        self.progress
+            .retain(|name, progress| progress.finished.is_some() || teams.teams.contains_key(name));
+
+        let mut events = Vec::new();
+        let total = self.checkpoints.len();
+        for (name, team) in teams.teams.iter() {
+            let progress = self.progress.entry(name.clone()).or_default();
+            if progress.finished.is_some() || total == 0 {
+                continue;
+            }
+
+            let finished = match self.tracking {
+                Tracking::Boids => {
+                    progress
+                        .boids
+                        .retain(|id, _| team.flock.boids.contains_key(id));
+                    for (id, boid) in team.flock.boids.iter() {
+                        let next = progress.boids.entry(*id).or_insert(0);
+                        if *next < total && self.checkpoints[*next].reached_by(boid.x, boid.y) {
+                            *next += 1;
+                            progress.checkpoints += 1;
+                        }
+                    }
+                    let finishers = progress
+                        .boids
+                        .values()
+                        .filter(|&&next| next == total)
+                        .count();
+                    finishers >= self.finishers.max(1)
+                }
+                Tracking::Centroid => {
+                    let boids = &team.flock.boids;
+                    let cx = circular_mean(boids.values().map(|boid| boid.x));
+                    let cy = circular_mean(boids.values().map(|boid| boid.y));
+                    if let (Some(cx), Some(cy)) = (cx, cy) {
+                        if self.checkpoints[progress.checkpoints].reached_by(cx, cy) {
+                            progress.checkpoints += 1;
+                        }
+                    }
+                    progress.checkpoints == total
+                }
+            };
+
+            if finished {
+                progress.finished = Some(tick);
+                events.push(Event::Finished {
+                    team: name.clone(),
+                    tick,
+                });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Boid, Team};
+
+    fn teams_with(boid: Boid) -> Teams {
+        let mut teams = Teams::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock.boids.insert(FlockId::from(1), boid);
+        teams.teams.insert("TEST".to_owned(), team);
+        teams
+    }
+
+    #[test]
+    fn checkpoints_should_be_passed_in_order() {
+        let mut race = Race::new(
+            vec![
+                Checkpoint::new(0.2, 0.2, 0.05),
+                Checkpoint::new(0.8, 0.8, 0.05),
+            ],
+            Tracking::Boids,
+            1,
+        );
+
+        race.step(&teams_with(Boid::new(0.8, 0.8, 0.0, 0.0)), 1);
+        assert_eq!(race.progress()["TEST"].checkpoints, 0);
+
+        race.step(&teams_with(Boid::new(0.2, 0.2, 0.0, 0.0)), 2);
+        assert_eq!(race.progress()["TEST"].checkpoints, 1);
+
+        let events = race.step(&teams_with(Boid::new(0.8, 0.8, 0.0, 0.0)), 3);
+        assert_eq!(
+            events,
+            vec![Event::Finished {
+                team: "TEST".to_owned(),
+                tick: 3,
+            }]
+        );
+        assert_eq!(race.progress()["TEST"].finished, Some(3));
+    }
+
+    #[test]
+    fn finished_teams_should_keep_their_result_after_they_unregister() {
+        let mut race = Race::new(vec![Checkpoint::new(0.2, 0.2, 0.05)], Tracking::Boids, 1);
+        race.step(&teams_with(Boid::new(0.2, 0.2, 0.0, 0.0)), 4);
+
+        race.step(&Teams::new(), 5);
+
+        assert_eq!(race.progress()["TEST"].finished, Some(4));
+    }
+}
@@ -0,0 +1,156 @@
+use random::{self, Source};
+
+use super::{spatial::SpatialIndex, Boid, FlockId, Teams, SPREAD};
+
+pub struct Reproduction {
+    rate: f64,
+    energy_threshold: Option<f64>,
+    mate_radius: Option<f64>,
+    mutation: f64,
+}
+
+impl Reproduction {
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            energy_threshold: None,
+            mate_radius: None,
+            mutation: 0f64,
+        }
+    }
+
+    pub fn energy_threshold(mut self, energy_threshold: Option<f64>) -> Self {
+        self.energy_threshold = energy_threshold;
+        self
+    }
+
+    pub fn mate_radius(mut self, mate_radius: Option<f64>) -> Self {
+        self.mate_radius = mate_radius;
+        self
+    }
+
+    pub fn mutation(mut self, mutation: f64) -> Self {
+        self.mutation = mutation;
+        self
+    }
+
+    pub fn step(&self, teams: &mut Teams, index: &SpatialIndex) {
+        let mut source = random::default();
+        for (name, team) in teams.teams.iter_mut() {
+            let phenotype = team.role.phenotype();
+            let flock = &mut team.flock;
+            let mut parents: Vec<FlockId> = flock
+                .boids
+                .iter()
+                .filter(|(_, boid)| {
+                    self.energy_threshold
+                        .is_none_or(|threshold| boid.energy >= threshold)
+                })
+                .filter(|(id, boid)| match self.mate_radius {
+                    Some(radius) => index
+                        .neighbours_within(boid.x, boid.y, radius)
+                        .into_iter()
+                        .any(|entry| {
+                            entry.id != **id
+                                && index.team(entry) == name
+                                && flock.boids.contains_key(&entry.id)
+                        }),
+                    None => true,
+                })
+                .map(|(id, _)| *id)
+                .collect();
+            parents.sort_by_key(|id| id.0);
+
+            for id in parents {
+                if flock.is_full() {
+                    break;
+                }
+                if source.read_f64() >= self.rate {
+                    continue;
+                }
+                let parent = flock.boids.get_mut(&id).unwrap(/* safe because parents are alive */);
+                if self.energy_threshold.is_some() {
+                    parent.energy /= 2f64;
+                }
+                let mut child = Boid::new(
+                    parent.x + SPREAD * (source.read_f64() - 0.5),
+                    parent.y + SPREAD * (source.read_f64() - 0.5),
+                    parent.heading,
+                    0f64,
+                );
+                child.energy = parent.energy;
+                let inherited = parent.phenotype.unwrap_or(phenotype);
+                child.phenotype = if self.mutation > 0f64 {
+                    Some(inherited.mutate(self.mutation, &mut source))
+                } else {
+                    parent.phenotype
+                };
+                child.normalize(&inherited);
+
+                let mut identifier = source.read::<FlockId>();
+                while flock.boids.contains_key(&identifier) {
+                    identifier = source.read::<FlockId>();
+                }
+                flock.boids.insert(identifier, child);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Team;
+
+    fn teams_with(boids: Vec<Boid>) -> Teams {
+        let mut teams = Teams::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock.set_population_cap(5);
+        for (id, boid) in boids.into_iter().enumerate() {
+            team.flock.boids.insert(FlockId::from(id as u64), boid);
+        }
+        teams.teams.insert("TEST".to_owned(), team);
+        teams
+    }
+
+    #[test]
+    fn boids_should_reproduce_up_to_the_population_cap() {
+        let mut teams = teams_with(vec![
+            Boid::new(0.2, 0.2, 0.0, 0.0),
+            Boid::new(0.5, 0.5, 0.0, 0.0),
+            Boid::new(0.8, 0.8, 0.0, 0.0),
+        ]);
+        let reproduction = Reproduction::new(1f64)
+            .energy_threshold(Some(0.8))
+            .mutation(0.1);
+        let index = SpatialIndex::of(&teams, 0.02);
+
+        reproduction.step(&mut teams, &index);
+
+        let boids = &teams.teams["TEST"].flock.boids;
+        assert_eq!(boids.len(), 5);
+        assert_eq!(
+            boids
+                .values()
+                .filter(|boid| boid.phenotype.is_some())
+                .count(),
+            2
+        );
+        assert_eq!(boids.values().filter(|boid| boid.energy == 0.5).count(), 4);
+    }
+
+    #[test]
+    fn boids_without_a_mate_nearby_should_not_reproduce() {
+        let mut teams = teams_with(vec![
+            Boid::new(0.2, 0.2, 0.0, 0.0),
+            Boid::new(0.5, 0.5, 0.0, 0.0),
+            Boid::new(0.505, 0.5, 0.0, 0.0),
+        ]);
+        let reproduction = Reproduction::new(1f64).mate_radius(Some(0.01));
+        let index = SpatialIndex::of(&teams, 0.02);
+
+        reproduction.step(&mut teams, &index);
+
+        assert_eq!(teams.teams["TEST"].flock.boids.len(), 5);
+    }
+}
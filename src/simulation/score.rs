@@ -0,0 +1,242 @@
+use std::{collections::HashMap, f64::consts::SQRT_2};
+
+use super::{
+    geometry::{circular_mean, distance},
+    spatial::SpatialIndex,
+    Team,
+};
+
+const MINIMAL_CELL_SIZE: f64 = 0.05;
+
+/// A metric that is evaluated every tick for every team.
+///
+/// Scores are normalized to lie between 0 and 1, higher being better.
+pub trait Score {
+    fn name(&self) -> &'static str;
+    fn evaluate(&self, team: &Team) -> f64;
+}
+
+pub fn defaults() -> Vec<Box<dyn Score + Send>> {
+    vec![
+        Box::new(Cohesion),
+        Box::new(Alignment),
+        Box::new(Separation::new(0.01)),
+        Box::new(Coverage::new(10)),
+        Box::new(TimeAlive),
+    ]
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Scores {
+    pub metrics: HashMap<String, f64>,
+    pub aggregate: f64,
+}
+
+impl Scores {
+    pub fn evaluate(scores: &[Box<dyn Score + Send>], team: &Team) -> Self {
+        let metrics: HashMap<String, f64> = scores
+            .iter()
+            .map(|score| (score.name().to_string(), score.evaluate(team)))
+            .collect();
+        let aggregate = if metrics.is_empty() {
+            0f64
+        } else {
+            metrics.values().sum::<f64>() / metrics.len() as f64
+        };
+
+        Self { metrics, aggregate }
+    }
+}
+
+/// One minus the mean distance to the centroid, relative to the largest distance possible.
+pub struct Cohesion;
+
+impl Score for Cohesion {
+    fn name(&self) -> &'static str {
+        "cohesion"
+    }
+
+    fn evaluate(&self, team: &Team) -> f64 {
+        let boids = &team.flock.boids;
+        let cx = circular_mean(boids.values().map(|boid| boid.x));
+        let cy = circular_mean(boids.values().map(|boid| boid.y));
+        if let (Some(cx), Some(cy)) = (cx, cy) {
+            let total: f64 = boids
+                .values()
+                .map(|boid| distance(boid.x, boid.y, cx, cy))
+                .sum();
+            let mean = total / boids.len() as f64;
+            1f64 - mean / (SQRT_2 / 2f64)
+        } else {
+            0f64
+        }
+    }
+}
+
+/// Order parameter of the headings, i.e. the length of the mean heading vector.
+pub struct Alignment;
+
+impl Score for Alignment {
+    fn name(&self) -> &'static str {
+        "alignment"
+    }
+
+    fn evaluate(&self, team: &Team) -> f64 {
+        let boids = &team.flock.boids;
+        if boids.is_empty() {
+            return 0f64;
+        }
+        let (cos, sin) = boids.values().fold((0f64, 0f64), |(cos, sin), boid| {
+            (cos + boid.heading.cos(), sin + boid.heading.sin())
+        });
+        let n = boids.len() as f64;
+        ((cos / n).powi(2) + (sin / n).powi(2)).sqrt()
+    }
+}
+
+/// Fraction of pairs of boids that are not within `radius` of each other.
+pub struct Separation {
+    radius: f64,
+}
+
+impl Separation {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Score for Separation {
+    fn name(&self) -> &'static str {
+        "separation"
+    }
+
+    fn evaluate(&self, team: &Team) -> f64 {
+        let n = team.flock.boids.len();
+        if n < 2 {
+            return 1f64;
+        }
+        let mut index = SpatialIndex::new(self.radius.max(MINIMAL_CELL_SIZE));
+        let name = index.add_team(&team.name);
+        for (id, boid) in &team.flock.boids {
+            index.insert(name, *id, boid.x, boid.y);
+        }
+        let violations = index.pairs_within(self.radius).len();
+        let pairs = n * (n - 1) / 2;
+        1f64 - violations as f64 / pairs as f64
+    }
+}
+
+/// Fraction of the cells of a `cells` by `cells` grid that contain at least one boid.
+pub struct Coverage {
+    cells: usize,
+}
+
+impl Coverage {
+    /// Divides the world in `cells` by `cells` cells, at least one.
+    pub fn new(cells: usize) -> Self {
+        Self {
+            cells: cells.max(1),
+        }
+    }
+}
+
+impl Score for Coverage {
+    fn name(&self) -> &'static str {
+        "coverage"
+    }
+
+    fn evaluate(&self, team: &Team) -> f64 {
+        let cells = self.cells as f64;
+        let mut occupied: Vec<(usize, usize)> = team
+            .flock
+            .boids
+            .values()
+            .map(|boid| {
+                (
+                    ((boid.x * cells) as usize).min(self.cells - 1),
+                    ((boid.y * cells) as usize).min(self.cells - 1),
+                )
+            })
+            .collect();
+        occupied.sort();
+        occupied.dedup();
+        occupied.len() as f64 / (cells * cells)
+    }
+}
+
+/// Fraction of the ticks since registration during which the team had a living flock.
+pub struct TimeAlive;
+
+impl Score for TimeAlive {
+    fn name(&self) -> &'static str {
+        "time_alive"
+    }
+
+    fn evaluate(&self, team: &Team) -> f64 {
+        if team.age == 0 {
+            0f64
+        } else {
+            team.alive as f64 / team.age as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Boid, FlockId};
+
+    fn team_with(boids: Vec<Boid>) -> Team {
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        for (id, boid) in boids.into_iter().enumerate() {
+            team.flock.boids.insert(FlockId::from(id as u64), boid);
+        }
+        team
+    }
+
+    #[test]
+    fn flock_with_equal_headings_should_be_aligned() {
+        let team = team_with(vec![
+            Boid::new(0.1, 0.1, 1.0, 0.01),
+            Boid::new(0.5, 0.7, 1.0, 0.01),
+        ]);
+
+        let alignment = Alignment.evaluate(&team);
+
+        assert!((alignment - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cohesion_should_take_wrap_around_into_account() {
+        let team = team_with(vec![
+            Boid::new(0.001, 0.5, 0.0, 0.01),
+            Boid::new(0.999, 0.5, 0.0, 0.01),
+        ]);
+
+        let cohesion = Cohesion.evaluate(&team);
+
+        assert!(cohesion > 0.99);
+    }
+
+    #[test]
+    fn close_boids_should_violate_separation() {
+        let team = team_with(vec![
+            Boid::new(0.5, 0.5, 0.0, 0.01),
+            Boid::new(0.501, 0.5, 0.0, 0.01),
+            Boid::new(0.1, 0.1, 0.0, 0.01),
+        ]);
+
+        let separation = Separation::new(0.01).evaluate(&team);
+
+        assert!((separation - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn coverage_without_cells_should_use_a_single_cell() {
+        let team = team_with(vec![Boid::new(0.999, 0.999, 0.0, 0.01)]);
+
+        let coverage = Coverage::new(0).evaluate(&team);
+
+        assert!((coverage - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use super::{Control, Flock, Role, Team, Teams};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Snapshot {
+    teams: Vec<TeamRecord>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct TeamRecord {
+    name: String,
+    ip_address: String,
+    port: u16,
+    #[serde(default)]
+    role: Role,
+    #[serde(default)]
+    control: Control,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flock: Option<Flock>,
+}
+
+impl Teams {
+    pub fn snapshot(&self, include_flocks: bool) -> Snapshot {
+        let teams = self
+            .teams
+            .values()
+            .map(|team| TeamRecord {
+                name: team.name.clone(),
+                ip_address: team.ip_address.clone(),
+                port: team.port,
+                role: team.role,
+                control: team.control,
+                token: Some(team.token.clone()),
+                flock: if include_flocks {
+                    Some(team.flock.clone())
+                } else {
+                    None
+                },
+            })
+            .collect();
+
+        Snapshot { teams }
+    }
+}
+
+impl From<Snapshot> for Teams {
+    fn from(snapshot: Snapshot) -> Self {
+        let teams: HashMap<String, Team> = snapshot
+            .teams
+            .into_iter()
+            .map(|record| {
+                let mut team = Team::new(record.name, record.ip_address, record.port);
+                team.role = record.role;
+                team.control = record.control;
+                if let Some(token) = record.token {
+                    team.token = token;
+                }
+                if let Some(flock) = record.flock {
+                    team.flock = flock;
+                }
+                (team.name.clone(), team)
+            })
+            .collect();
+
+        let mut restored = Teams::new();
+        restored.teams = teams;
+        restored
+    }
+}
@@ -0,0 +1,170 @@
+use super::{geometry::distance, FlockId, Teams};
+
+const CELL_SIZE: f64 = 0.02;
+
+/// Uniform grid over the unit torus that answers neighbour queries without
+/// comparing every pair of boids.
+pub struct SpatialIndex {
+    cells_per_side: usize,
+    cells: Vec<Vec<usize>>,
+    entries: Vec<Entry>,
+    teams: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    pub team: usize,
+    pub id: FlockId,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f64) -> Self {
+        let cells_per_side = ((1f64 / cell_size).floor() as usize).max(1);
+        Self {
+            cells_per_side,
+            cells: vec![Vec::new(); cells_per_side * cells_per_side],
+            entries: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    pub fn of(teams: &Teams, cell_size: f64) -> Self {
+        let mut index = Self::new(cell_size);
+        index.rebuild(teams);
+        index
+    }
+
+    pub fn rebuild(&mut self, teams: &Teams) {
+        self.clear();
+        let mut names: Vec<&String> = teams.teams.keys().collect();
+        names.sort();
+        for name in names {
+            let team = self.add_team(name);
+            for (id, boid) in &teams.teams[name].flock.boids {
+                self.insert(team, *id, boid.x, boid.y);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|cell| cell.clear());
+        self.entries.clear();
+        self.teams.clear();
+    }
+
+    pub fn add_team(&mut self, name: &str) -> usize {
+        self.teams.push(name.to_string());
+        self.teams.len() - 1
+    }
+
+    pub fn insert(&mut self, team: usize, id: FlockId, x: f64, y: f64) {
+        let cell = self.cell_of(x, y);
+        self.cells[cell].push(self.entries.len());
+        self.entries.push(Entry { team, id, x, y });
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn team(&self, entry: &Entry) -> &str {
+        &self.teams[entry.team]
+    }
+
+    pub fn neighbours_within(&self, x: f64, y: f64, radius: f64) -> Vec<&Entry> {
+        self.query(x, y, radius)
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .collect()
+    }
+
+    pub fn pairs_within(&self, radius: f64) -> Vec<(&Entry, &Entry)> {
+        let mut pairs = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            for other in self.query(entry.x, entry.y, radius) {
+                if other > index {
+                    pairs.push((entry, &self.entries[other]));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn query(&self, x: f64, y: f64, radius: f64) -> Vec<usize> {
+        let n = self.cells_per_side as i64;
+        let reach = (radius * n as f64).ceil() as i64;
+        let cell = self.cell_of(x, y) as i64;
+        let (column, row) = (cell % n, cell / n);
+        let columns = wrapped_range(column, reach, n);
+        let rows = wrapped_range(row, reach, n);
+
+        let mut result = Vec::new();
+        for row in &rows {
+            for column in &columns {
+                for &index in &self.cells[row * self.cells_per_side + column] {
+                    let entry = &self.entries[index];
+                    if distance(x, y, entry.x, entry.y) <= radius {
+                        result.push(index);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn cell_of(&self, x: f64, y: f64) -> usize {
+        let n = self.cells_per_side;
+        let column = ((x.rem_euclid(1f64) * n as f64) as usize).min(n - 1);
+        let row = ((y.rem_euclid(1f64) * n as f64) as usize).min(n - 1);
+        row * n + column
+    }
+}
+
+fn wrapped_range(center: i64, reach: i64, n: i64) -> Vec<usize> {
+    if 2 * reach + 1 >= n {
+        return (0..n as usize).collect();
+    }
+    (center - reach..=center + reach)
+        .map(|index| index.rem_euclid(n) as usize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbours_should_be_found_across_the_edges() {
+        let mut index = SpatialIndex::new(0.1);
+        let team = index.add_team("TEST");
+        index.insert(team, FlockId::from(1), 0.99, 0.99);
+        index.insert(team, FlockId::from(2), 0.01, 0.01);
+        index.insert(team, FlockId::from(3), 0.5, 0.5);
+
+        let neighbours = index.neighbours_within(0.0, 0.0, 0.05);
+
+        let mut ids: Vec<FlockId> = neighbours.iter().map(|entry| entry.id).collect();
+        ids.sort_by_key(|id| format!("{:?}", id));
+        assert_eq!(ids, vec![FlockId::from(1), FlockId::from(2)]);
+    }
+
+    #[test]
+    fn pairs_should_be_reported_once() {
+        let mut index = SpatialIndex::new(0.1);
+        let team = index.add_team("TEST");
+        index.insert(team, FlockId::from(1), 0.5, 0.5);
+        index.insert(team, FlockId::from(2), 0.51, 0.5);
+
+        let pairs = index.pairs_within(0.02);
+
+        assert_eq!(pairs.len(), 1);
+    }
+}
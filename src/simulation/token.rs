@@ -0,0 +1,53 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Unguessable token built from the randomly keyed hasher of the standard library.
+pub fn generate() -> String {
+    let state = RandomState::new();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    (0..2u64)
+        .map(|part| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(part);
+            hasher.write_u64(count);
+            hasher.write_u64(nanos);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Compares a token in constant time, so its value can not be guessed byte by
+/// byte from how long a rejection takes.
+pub fn matches(token: &str, candidate: &str) -> bool {
+    token.len() == candidate.len()
+        && token
+            .bytes()
+            .zip(candidate.bytes())
+            .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_same_token_should_match() {
+        let token = generate();
+
+        assert!(matches(&token, &token.clone()));
+        assert!(!matches(&token, &generate()));
+        assert!(!matches(&token, &token[1..]));
+        assert!(!matches(&token, ""));
+    }
+}
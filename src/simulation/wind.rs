@@ -0,0 +1,248 @@
+use std::{f64::consts::PI, str::FromStr};
+
+use super::{geometry::delta, Teams};
+
+pub struct Wind {
+    currents: Vec<Current>,
+    tick: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Current {
+    Uniform {
+        vx: f64,
+        vy: f64,
+    },
+    Vortex {
+        x: f64,
+        y: f64,
+        strength: f64,
+        radius: f64,
+    },
+    Noise {
+        strength: f64,
+        cells: u64,
+        period: u64,
+    },
+    Gust {
+        vx: f64,
+        vy: f64,
+        period: u64,
+    },
+}
+
+impl FromStr for Wind {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        source
+            .split(';')
+            .map(|current| current.trim().parse::<Current>())
+            .collect::<Result<Vec<Current>, String>>()
+            .map(Wind::new)
+    }
+}
+
+impl FromStr for Current {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let error = || format!("unknown current \"{}\"", source);
+        let (kind, parameters) = source.split_once(':').ok_or_else(error)?;
+        let parameters = parameters
+            .split(',')
+            .map(|parameter| parameter.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| error())?;
+        if !parameters.iter().all(|parameter| parameter.is_finite()) {
+            return Err(format!(
+                "current \"{}\" should only have finite numbers",
+                source
+            ));
+        }
+        let at_least = |value: f64, minimum: f64, name: &str| {
+            if value >= minimum {
+                Ok(value)
+            } else {
+                Err(format!(
+                    "{} of current \"{}\" should be at least {}",
+                    name, source, minimum
+                ))
+            }
+        };
+        match (kind, parameters.as_slice()) {
+            ("uniform", [vx, vy]) => Ok(Current::Uniform { vx: *vx, vy: *vy }),
+            ("vortex", [_, _, _, radius]) if *radius <= 0f64 => Err(format!(
+                "radius of current \"{}\" should be positive",
+                source
+            )),
+            ("vortex", [x, y, strength, radius]) => Ok(Current::Vortex {
+                x: *x,
+                y: *y,
+                strength: *strength,
+                radius: *radius,
+            }),
+            ("noise", [strength, cells, period]) => Ok(Current::Noise {
+                strength: *strength,
+                cells: at_least(*cells, 1f64, "cells")? as u64,
+                period: at_least(*period, 1f64, "period")? as u64,
+            }),
+            ("gust", [vx, vy, period]) => Ok(Current::Gust {
+                vx: *vx,
+                vy: *vy,
+                period: at_least(*period, 1f64, "period")? as u64,
+            }),
+            _ => Err(error()),
+        }
+    }
+}
+
+impl Wind {
+    pub fn new(currents: Vec<Current>) -> Self {
+        Self { currents, tick: 0 }
+    }
+
+    pub fn sample(&self, x: f64, y: f64) -> (f64, f64) {
+        self.currents
+            .iter()
+            .map(|current| current.sample(x, y, self.tick))
+            .fold((0f64, 0f64), |(vx, vy), (dvx, dvy)| (vx + dvx, vy + dvy))
+    }
+
+    pub fn step(&mut self, teams: &mut Teams) {
+        self.tick += 1;
+        for team in teams.teams.values_mut() {
+            for boid in team.flock.boids.values_mut() {
+                let (vx, vy) = self.sample(boid.x, boid.y);
+                boid.x = (boid.x + vx).rem_euclid(1f64);
+                boid.y = (boid.y + vy).rem_euclid(1f64);
+                boid.drift = Some((vx, vy));
+            }
+        }
+    }
+}
+
+impl Current {
+    fn sample(&self, x: f64, y: f64, tick: u64) -> (f64, f64) {
+        match *self {
+            Current::Uniform { vx, vy } => (vx, vy),
+            Current::Vortex {
+                x: cx,
+                y: cy,
+                strength,
+                radius,
+            } => {
+                let (dx, dy) = (delta(cx, x), delta(cy, y));
+                let d = (dx * dx + dy * dy).sqrt();
+                if d == 0f64 {
+                    return (0f64, 0f64);
+                }
+                let falloff = (-(d / radius).powi(2)).exp();
+                (-strength * falloff * dy / d, strength * falloff * dx / d)
+            }
+            Current::Noise {
+                strength,
+                cells,
+                period,
+            } => {
+                let layer = tick / period;
+                let t = smooth((tick % period) as f64 / period as f64);
+                let sample = |salt: u64| {
+                    let now = noise(x, y, cells, layer, salt);
+                    let next = noise(x, y, cells, layer + 1, salt);
+                    strength * (2f64 * lerp(now, next, t) - 1f64)
+                };
+                (sample(0), sample(1))
+            }
+            Current::Gust { vx, vy, period } => {
+                let phase = 2f64 * PI * (tick % period) as f64 / period as f64;
+                let factor = 0.5 * (1f64 - phase.cos());
+                (factor * vx, factor * vy)
+            }
+        }
+    }
+}
+
+/// Value noise on a lattice that wraps around the edges of the world.
+fn noise(x: f64, y: f64, cells: u64, layer: u64, salt: u64) -> f64 {
+    let (x, y) = (
+        x.rem_euclid(1f64) * cells as f64,
+        y.rem_euclid(1f64) * cells as f64,
+    );
+    let (i, j) = (x.floor() as u64 % cells, y.floor() as u64 % cells);
+    let (tx, ty) = (smooth(x.fract()), smooth(y.fract()));
+    let corner = |i: u64, j: u64| hash(i % cells, j % cells, layer, salt);
+    lerp(
+        lerp(corner(i, j), corner(i + 1, j), tx),
+        lerp(corner(i, j + 1), corner(i + 1, j + 1), tx),
+        ty,
+    )
+}
+
+fn hash(i: u64, j: u64, layer: u64, salt: u64) -> f64 {
+    let mut h = i
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        .wrapping_add(j.wrapping_mul(0xC2B2_AE3D_27D4_EB4F))
+        .wrapping_add(layer.wrapping_mul(0x1656_67B1_9E37_79F9))
+        .wrapping_add(salt.wrapping_mul(0x27D4_EB2F_1656_67C5));
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn smooth(t: f64) -> f64 {
+    t * t * (3f64 - 2f64 * t)
+}
+
+fn lerp(from: f64, to: f64, t: f64) -> f64 {
+    from + (to - from) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Boid, FlockId, Team};
+
+    #[test]
+    fn boids_should_drift_with_the_wind() {
+        let mut teams = Teams::new();
+        let mut team = Team::new("TEST", "TEST ADDRESS", 2643);
+        team.flock
+            .boids
+            .insert(FlockId::from(1), Boid::new(0.995, 0.5, 0.0, 0.0));
+        teams.teams.insert("TEST".to_owned(), team);
+        let mut wind: Wind = "uniform:0.01,0".parse().unwrap();
+
+        wind.step(&mut teams);
+
+        let boid = &teams.teams["TEST"].flock.boids[&FlockId::from(1)];
+        assert!((boid.x - 0.005).abs() < 1e-9);
+        assert_eq!(boid.drift, Some((0.01, 0f64)));
+    }
+
+    #[test]
+    fn noise_should_be_continuous_across_the_edges() {
+        let wind: Wind = "noise:0.01,4,100".parse().unwrap();
+
+        let (left, _) = wind.sample(0.99999, 0.3);
+        let (right, _) = wind.sample(0.00001, 0.3);
+
+        assert!((left - right).abs() < 1e-6);
+    }
+
+    #[test]
+    fn currents_out_of_range_should_be_rejected() {
+        for source in &[
+            "uniform:NaN,0",
+            "uniform:inf,0",
+            "vortex:0.5,0.5,0.01,0",
+            "noise:0.01,0,100",
+            "noise:0.01,4,0",
+            "gust:0.01,0,0.5",
+        ] {
+            assert!(source.parse::<Wind>().is_err(), "{} was accepted", source);
+        }
+        assert!("vortex:0.5,0.5,-0.01,0.1".parse::<Wind>().is_ok());
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    io, process,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::{Handle, Signals},
+};
+
+/// Runs the subsystems of the server on their own threads and stops them all
+/// when the process is signalled or when one of them stops by itself.
+///
+/// Subsystems are stopped in the reverse order in which they were spawned, so
+/// a subsystem can rely on the ones spawned before it while it stops. A second
+/// signal while they stop exits the process right away.
+pub struct Supervisor {
+    tx: Sender<Event>,
+    rx: Receiver<Event>,
+    subsystems: Vec<Subsystem>,
+    grace: Duration,
+    signals: Option<(Handle, JoinHandle<()>)>,
+}
+
+struct Subsystem {
+    name: String,
+    stop: Option<Box<dyn FnOnce() + Send>>,
+    running: bool,
+}
+
+enum Event {
+    Signal(i32),
+    Shutdown,
+    Exited { name: String, panicked: bool },
+}
+
+/// Asks a running supervisor to stop its subsystems.
+#[derive(Clone)]
+pub struct Trigger {
+    tx: Sender<Event>,
+}
+
+impl Trigger {
+    pub fn shutdown(&self) {
+        if self.tx.send(Event::Shutdown).is_err() {
+            error!("could not trigger shutdown");
+        }
+    }
+}
+
+/// Reports the end of a subsystem thread, whether it returned or panicked.
+struct Exit {
+    name: String,
+    tx: Sender<Event>,
+}
+
+impl Drop for Exit {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Event::Exited {
+            name: self.name.clone(),
+            panicked: thread::panicking(),
+        });
+    }
+}
+
+/// A supervisor dropped before it supervises, because starting the server failed
+/// halfway, stops the subsystems it already spawned.
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            tx,
+            rx,
+            subsystems: Vec::new(),
+            grace: Duration::from_secs(5),
+            signals: None,
+        }
+    }
+
+    /// How long to wait for each subsystem to stop.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Spawns a subsystem that is asked to stop by calling `stop`.
+    pub fn spawn<F, S>(&mut self, name: &str, run: F, stop: S) -> io::Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+        S: FnOnce() + Send + 'static,
+    {
+        let exit = Exit {
+            name: name.to_string(),
+            tx: self.tx.clone(),
+        };
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                let _exit = exit;
+                run();
+            })?;
+        self.subsystems.push(Subsystem {
+            name: name.to_string(),
+            stop: Some(Box::new(stop)),
+            running: true,
+        });
+        Ok(())
+    }
+
+    pub fn trigger(&self) -> Trigger {
+        Trigger {
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Turns SIGINT and SIGTERM into a shutdown, until the subsystems have
+    /// stopped.
+    pub fn handle_signals(&mut self) -> io::Result<()> {
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let handle = signals.handle();
+        let tx = self.tx.clone();
+        let thread = thread::Builder::new()
+            .name("signals".to_string())
+            .spawn(move || {
+                for signal in signals.forever() {
+                    if tx.send(Event::Signal(signal)).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        self.signals = Some((handle, thread));
+        Ok(())
+    }
+
+    /// Waits for a signal or for a subsystem to stop, then stops the others.
+    ///
+    /// Returns an error when the shutdown was caused by a subsystem stopping
+    /// by itself, or when the subsystems did not stop in time.
+    pub fn supervise(mut self) -> Result<(), String> {
+        let cause = match self.rx.recv() {
+            Ok(Event::Signal(signal)) => {
+                info!("received signal {}, shutting down", signal);
+                None
+            }
+            Ok(Event::Shutdown) => {
+                info!("shutting down");
+                None
+            }
+            Ok(Event::Exited { name, panicked }) => {
+                let cause = if panicked {
+                    format!("{} panicked", name)
+                } else {
+                    format!("{} stopped unexpectedly", name)
+                };
+                error!("{}, shutting down", cause);
+                self.exited(&name);
+                Some(cause)
+            }
+            Err(_) => Some("lost track of the subsystems".to_string()),
+        };
+        let stopped = self.shutdown();
+        match (cause, stopped) {
+            (Some(cause), _) => Err(cause),
+            (None, stopped) => stopped,
+        }
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        let mut subsystems: Vec<(String, Box<dyn FnOnce() + Send>)> = self
+            .subsystems
+            .iter_mut()
+            .filter(|subsystem| subsystem.running)
+            .filter_map(|subsystem| {
+                let name = subsystem.name.clone();
+                subsystem.stop.take().map(|stop| (name, stop))
+            })
+            .collect();
+        let mut result = Ok(());
+        while let Some((name, stop)) = subsystems.pop() {
+            info!("stopping {}", name);
+            stop();
+            if let Err(error) = self.wait_for(&name) {
+                error!("{}", error);
+                result = Err(error);
+            }
+        }
+        if let Some((handle, thread)) = self.signals.take() {
+            handle.close();
+            if thread.join().is_err() {
+                error!("signal handling panicked");
+            }
+        }
+        result
+    }
+
+    fn wait_for(&mut self, name: &str) -> Result<(), String> {
+        let deadline = Instant::now() + self.grace;
+        while self.is_running(name) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.rx.recv_timeout(remaining) {
+                Ok(Event::Exited {
+                    name: exited,
+                    panicked,
+                }) => {
+                    if panicked {
+                        error!("{} panicked while stopping", exited);
+                    }
+                    self.exited(&exited);
+                }
+                Ok(Event::Signal(signal)) => {
+                    error!(
+                        "received signal {} while stopping {}, exiting now",
+                        signal, name
+                    );
+                    process::exit(1);
+                }
+                Ok(Event::Shutdown) => (),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("{} did not stop in time", name));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("lost track of the subsystems".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_running(&self, name: &str) -> bool {
+        self.subsystems
+            .iter()
+            .any(|subsystem| subsystem.name == name && subsystem.running)
+    }
+
+    fn exited(&mut self, name: &str) {
+        for subsystem in self.subsystems.iter_mut() {
+            if subsystem.name == name {
+                subsystem.running = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsystems_should_stop_in_reverse_order_on_a_signal() {
+        let (order_tx, order_rx) = channel();
+        let mut supervisor = Supervisor::new().grace(Duration::from_secs(1));
+        for name in &["first", "second"] {
+            let (stop_tx, stop_rx) = channel::<()>();
+            let order_tx = order_tx.clone();
+            supervisor
+                .spawn(
+                    name,
+                    move || {
+                        stop_rx.recv().unwrap();
+                        order_tx.send(*name).unwrap();
+                    },
+                    move || stop_tx.send(()).unwrap(),
+                )
+                .unwrap();
+        }
+        supervisor.tx.send(Event::Signal(SIGTERM)).unwrap();
+
+        assert_eq!(supervisor.supervise(), Ok(()));
+        assert_eq!(
+            order_rx.try_iter().collect::<Vec<_>>(),
+            vec!["second", "first"]
+        );
+    }
+
+    #[test]
+    fn signal_handling_should_stop_with_the_subsystems() {
+        let mut supervisor = Supervisor::new().grace(Duration::from_secs(1));
+        supervisor.handle_signals().unwrap();
+        supervisor.trigger().shutdown();
+
+        assert_eq!(supervisor.supervise(), Ok(()));
+    }
+
+    #[test]
+    fn a_dropped_supervisor_should_stop_its_subsystems() {
+        let (stopped_tx, stopped_rx) = channel();
+        let (stop_tx, stop_rx) = channel::<()>();
+        let mut supervisor = Supervisor::new().grace(Duration::from_secs(1));
+        supervisor
+            .spawn(
+                "abandoned",
+                move || {
+                    stop_rx.recv().unwrap();
+                    stopped_tx.send(()).unwrap();
+                },
+                move || stop_tx.send(()).unwrap(),
+            )
+            .unwrap();
+
+        drop(supervisor);
+
+        assert_eq!(stopped_rx.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn a_panicking_subsystem_should_shut_down_the_others() {
+        let (stop_tx, stop_rx) = channel::<()>();
+        let mut supervisor = Supervisor::new().grace(Duration::from_secs(1));
+        supervisor
+            .spawn(
+                "steady",
+                move || stop_rx.recv().unwrap(),
+                move || stop_tx.send(()).unwrap(),
+            )
+            .unwrap();
+        supervisor
+            .spawn("fragile", || panic!("crash"), || ())
+            .unwrap();
+
+        assert_eq!(supervisor.supervise(), Err("fragile panicked".to_string()));
+    }
+}
@@ -1,3 +1,7 @@
+use serde_json::Value;
+
 pub enum Message {
-    Update(String),
+    Update(Value),
+    Events(Value),
+    Stop,
 }
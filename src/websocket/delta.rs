@@ -0,0 +1,235 @@
+use serde_json::{json, Map, Value};
+
+pub const KEYFRAME_INTERVAL: u64 = 30;
+
+/// Boid fields that change every tick. They are only sent in keyframes and with new boids.
+const PER_TICK: &[&str] = &["age"];
+
+/// Turns consecutive updates into a keyframe followed by deltas against the previous update.
+pub struct Compressor {
+    keyframe_interval: u64,
+    frames: u64,
+    previous: Option<Map<String, Value>>,
+}
+
+impl Compressor {
+    pub fn new(keyframe_interval: u64) -> Self {
+        Self {
+            keyframe_interval: keyframe_interval.max(1),
+            frames: 0,
+            previous: None,
+        }
+    }
+
+    pub fn compress(&mut self, current: Value) -> Option<Value> {
+        let current = match current {
+            Value::Object(current) => current,
+            _ => return None,
+        };
+        let keyframe = self.frames.is_multiple_of(self.keyframe_interval);
+        self.frames += 1;
+
+        let frame = match &self.previous {
+            Some(previous) if !keyframe => delta(previous, &current),
+            _ => {
+                let mut frame = current.clone();
+                frame.insert("keyframe".to_string(), Value::Bool(true));
+                Some(Value::Object(frame))
+            }
+        };
+        self.previous = Some(current);
+        frame
+    }
+
+    /// Makes the next frame a keyframe.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+fn delta(previous: &Map<String, Value>, current: &Map<String, Value>) -> Option<Value> {
+    let empty = Map::new();
+    let teams_of = |update: &Map<String, Value>| {
+        update
+            .get("teams")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let (previous_teams, current_teams) = (teams_of(previous), teams_of(current));
+
+    let mut teams = Map::new();
+    for (name, team) in &current_teams {
+        let previous_team = previous_teams
+            .get(name)
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        if let Some(team_delta) = team
+            .as_object()
+            .and_then(|team| team_delta(previous_team, team))
+        {
+            teams.insert(name.clone(), team_delta);
+        }
+    }
+    let removed_teams: Vec<&String> = previous_teams
+        .keys()
+        .filter(|name| !current_teams.contains_key(*name))
+        .collect();
+
+    let mut world = Map::new();
+    for (key, value) in current.iter().filter(|(key, _)| *key != "teams") {
+        if previous.get(key) != Some(value) {
+            world.insert(key.clone(), value.clone());
+        }
+    }
+    for key in previous.keys().filter(|key| !current.contains_key(*key)) {
+        world.insert(key.clone(), Value::Null);
+    }
+
+    if teams.is_empty() && removed_teams.is_empty() && world.is_empty() {
+        return None;
+    }
+    Some(json!({
+        "delta": {
+            "teams": teams,
+            "removed_teams": removed_teams,
+            "world": world,
+        }
+    }))
+}
+
+fn team_delta(previous: &Map<String, Value>, current: &Map<String, Value>) -> Option<Value> {
+    let mut fields = Map::new();
+    for (key, value) in current.iter().filter(|(key, _)| *key != "flock") {
+        if previous.get(key) != Some(value) {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+
+    let empty = Map::new();
+    let boids_of = |team: &Map<String, Value>| {
+        team.get("flock")
+            .and_then(|flock| flock.get("boids"))
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_else(|| empty.clone())
+    };
+    let (previous_boids, current_boids) = (boids_of(previous), boids_of(current));
+    let mut boids = Map::new();
+    for (id, boid) in &current_boids {
+        match (previous_boids.get(id).and_then(Value::as_object), boid) {
+            (Some(previous_boid), Value::Object(boid)) => {
+                if let Some(boid_delta) = boid_delta(previous_boid, boid) {
+                    boids.insert(id.clone(), boid_delta);
+                }
+            }
+            _ => {
+                boids.insert(id.clone(), boid.clone());
+            }
+        }
+    }
+    let removed: Vec<&String> = previous_boids
+        .keys()
+        .filter(|id| !current_boids.contains_key(*id))
+        .collect();
+
+    if fields.is_empty() && boids.is_empty() && removed.is_empty() {
+        return None;
+    }
+    Some(json!({
+        "fields": fields,
+        "boids": boids,
+        "removed": removed,
+    }))
+}
+
+fn boid_delta(previous: &Map<String, Value>, current: &Map<String, Value>) -> Option<Value> {
+    let mut fields = Map::new();
+    for (key, value) in current
+        .iter()
+        .filter(|(key, _)| !PER_TICK.contains(&key.as_str()))
+    {
+        if previous.get(key) != Some(value) {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+    for key in previous.keys().filter(|key| !current.contains_key(*key)) {
+        fields.insert(key.clone(), Value::Null);
+    }
+
+    if fields.is_empty() {
+        None
+    } else {
+        Some(Value::Object(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas_should_only_contain_changes() {
+        let mut compressor = Compressor::new(10);
+        let first = json!({"teams": {"TEST": {"name": "TEST", "connected": true, "flock": {"boids": {
+            "1": {"x": 0.1, "y": 0.1},
+            "2": {"x": 0.2, "y": 0.2},
+            "3": {"x": 0.3, "y": 0.3}
+        }}}}});
+        let second = json!({"teams": {"TEST": {"name": "TEST", "connected": true, "flock": {"boids": {
+            "1": {"x": 0.1, "y": 0.1},
+            "2": {"x": 0.25, "y": 0.2},
+            "4": {"x": 0.4, "y": 0.4}
+        }}}}});
+
+        let keyframe = compressor.compress(first).unwrap();
+        let delta = compressor.compress(second.clone()).unwrap();
+
+        assert_eq!(keyframe["keyframe"], Value::Bool(true));
+        assert_eq!(
+            delta,
+            json!({"delta": {
+                "teams": {"TEST": {
+                    "fields": {},
+                    "boids": {"2": {"x": 0.25}, "4": {"x": 0.4, "y": 0.4}},
+                    "removed": ["3"]
+                }},
+                "removed_teams": [],
+                "world": {}
+            }})
+        );
+        assert_eq!(compressor.compress(second), None);
+    }
+
+    #[test]
+    fn deltas_of_a_moving_flock_should_be_smaller_than_keyframes() {
+        let update = |tick: u64| {
+            let boids: Map<String, Value> = (0..50)
+                .map(|id| {
+                    let boid = json!({
+                        "x": 0.01 * id as f64 + 0.001 * tick as f64,
+                        "y": 0.5,
+                        "heading": 0.0,
+                        "speed": 0.001,
+                        "intent": {"Thrust": {"turn": 0.0, "thrust": 1.0}},
+                        "energy": 0.9,
+                        "age": tick,
+                        "phenotype": [0.1, 0.9, 0.01]
+                    });
+                    (id.to_string(), boid)
+                })
+                .collect();
+            json!({"teams": {"TEST": {"name": "TEST", "flock": {"boids": boids}}}})
+        };
+        let mut compressor = Compressor::new(10);
+
+        let keyframe = compressor.compress(update(0)).unwrap();
+        let delta = compressor.compress(update(1)).unwrap();
+
+        assert_eq!(
+            delta["delta"]["teams"]["TEST"]["boids"]["3"],
+            json!({"x": 0.031})
+        );
+        assert!(delta.to_string().len() * 4 < keyframe.to_string().len());
+    }
+}
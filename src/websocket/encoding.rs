@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use rmp_serde;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::Value;
+use ws::{Message, Request};
+
+const PROTOCOL_PREFIX: &str = "bws.";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "json" => Ok(Encoding::Json),
+            "msgpack" => Ok(Encoding::MessagePack),
+            _ => Err(format!("unknown encoding \"{}\"", source)),
+        }
+    }
+}
+
+impl Encoding {
+    /// Picks the encoding from a `bws.<encoding>` subprotocol or an `encoding` query parameter.
+    pub fn negotiate(request: &Request) -> (Self, Option<String>) {
+        let protocol = request.protocols().ok().and_then(|protocols| {
+            protocols
+                .into_iter()
+                .filter_map(|protocol| {
+                    protocol
+                        .strip_prefix(PROTOCOL_PREFIX)
+                        .and_then(|encoding| encoding.parse::<Encoding>().ok())
+                        .map(|encoding| (encoding, protocol.to_string()))
+                })
+                .next()
+        });
+        match protocol {
+            Some((encoding, protocol)) => (encoding, Some(protocol)),
+            None => (Self::from_query(request.resource()), None),
+        }
+    }
+
+    fn from_query(resource: &str) -> Self {
+        resource
+            .split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or("")
+            .split('&')
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(key, _)| *key == "encoding")
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn encode(self, value: &Value) -> Result<Message, String> {
+        match self {
+            Encoding::Json => Ok(Message::text(value.to_string())),
+            Encoding::MessagePack => rmp_serde::to_vec_named(&Compact(value))
+                .map(Message::binary)
+                .map_err(|error| error.to_string()),
+        }
+    }
+}
+
+/// Serializes a JSON value with its floating point numbers narrowed to `f32`.
+struct Compact<'a>(&'a Value);
+
+impl<'a> Serialize for Compact<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    serializer.serialize_u64(n)
+                } else if let Some(n) = n.as_i64() {
+                    serializer.serialize_i64(n)
+                } else {
+                    serializer.serialize_f32(n.as_f64().unwrap_or_default() as f32)
+                }
+            }
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(&Compact(value))?;
+                }
+                seq.end()
+            }
+            Value::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, &Compact(value))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Team, Teams};
+    use serde_json;
+
+    fn teams(boids: usize) -> Teams {
+        let mut teams = Teams::new();
+        for name in &["red", "green", "blue"] {
+            teams
+                .teams
+                .insert(name.to_string(), Team::new(*name, "127.0.0.1", 8000));
+            teams.spawn_in_team(name.to_string(), boids);
+        }
+        teams
+    }
+
+    #[test]
+    fn message_pack_should_round_trip_with_f32_precision() {
+        let teams = teams(100);
+        let value = serde_json::to_value(&teams).unwrap();
+
+        let decoded: Value = match Encoding::MessagePack.encode(&value).unwrap() {
+            Message::Binary(bytes) => rmp_serde::from_slice(&bytes).unwrap(),
+            message => panic!("expected a binary message, got {}", message),
+        };
+
+        for (name, team) in value["teams"].as_object().unwrap() {
+            for (id, boid) in team["flock"]["boids"].as_object().unwrap() {
+                let decoded = &decoded["teams"][name]["flock"]["boids"][id];
+                for coordinate in &["x", "y", "heading", "speed"] {
+                    let expected = boid[coordinate].as_f64().unwrap();
+                    let actual = decoded[coordinate].as_f64().unwrap();
+                    assert!((expected - actual).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn message_pack_should_be_smaller_than_json() {
+        let teams = teams(1000);
+        let json = serde_json::to_string(&teams).unwrap();
+        let value = serde_json::to_value(&teams).unwrap();
+
+        let size = match Encoding::MessagePack.encode(&value).unwrap() {
+            Message::Binary(bytes) => bytes.len(),
+            message => panic!("expected a binary message, got {}", message),
+        };
+
+        assert!(
+            size * 3 < json.len() * 2,
+            "message pack is {} bytes, json is {} bytes",
+            size,
+            json.len()
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::convert::TryInto;
+
+pub const MAX_PENDING: u64 = 4;
+pub const MAX_DROPPED: u64 = 100;
+
+/// Tracks the frames a client has not acknowledged yet.
+///
+/// Every frame is followed by a ping carrying its sequence number. The pong of
+/// the client acknowledges that it read every frame up to that number.
+#[derive(Debug, Default)]
+pub struct Flow {
+    sent: u64,
+    acknowledged: u64,
+    dropped: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Admission {
+    Send,
+    Drop,
+    Disconnect,
+}
+
+impl Flow {
+    pub fn admit(&mut self, max_pending: u64, max_dropped: u64) -> Admission {
+        if self.sent - self.acknowledged < max_pending {
+            self.dropped = 0;
+            Admission::Send
+        } else {
+            self.dropped += 1;
+            if self.dropped > max_dropped {
+                Admission::Disconnect
+            } else {
+                Admission::Drop
+            }
+        }
+    }
+
+    pub fn lagging(&self) -> bool {
+        self.dropped > 0
+    }
+
+    pub fn sent(&mut self) -> Vec<u8> {
+        self.sent += 1;
+        self.sent.to_be_bytes().to_vec()
+    }
+
+    pub fn acknowledge(&mut self, payload: &[u8]) {
+        if let Ok(sequence) = payload.try_into().map(u64::from_be_bytes) {
+            if sequence <= self.sent {
+                self.acknowledged = self.acknowledged.max(sequence);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_should_only_lag_a_bounded_number_of_frames() {
+        let mut flow = Flow::default();
+        let pings: Vec<Vec<u8>> = (0..2).map(|_| flow.sent()).collect();
+
+        assert_eq!(flow.admit(2, 1), Admission::Drop);
+        assert!(flow.lagging());
+        assert_eq!(flow.admit(2, 1), Admission::Disconnect);
+
+        flow.acknowledge(&pings[0]);
+
+        assert_eq!(flow.admit(2, 1), Admission::Send);
+        assert!(!flow.lagging());
+    }
+}
@@ -1,18 +1,59 @@
 pub mod communication;
+mod delta;
+mod encoding;
+mod flow;
+mod session;
+mod subscription;
 
 use std::{
-    sync::mpsc::{Receiver, Sender},
+    collections::{hash_map::Entry, HashMap},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
     thread,
+    time::Duration,
 };
 
-use serde_json::{self};
-use ws::{self, Message, WebSocket};
+use serde_json::{self, Value};
+use ws::{
+    self, CloseCode, Frame, Handler, Handshake as WsHandshake, Message, OpCode, Request, Response,
+    WebSocket,
+};
+
+use self::{
+    communication::Message as WsMessage,
+    encoding::Encoding,
+    flow::{Admission, Flow},
+    session::{Handshake, Role, Session},
+    subscription::{Stream, Subscription},
+};
+pub use self::{
+    delta::KEYFRAME_INTERVAL,
+    flow::{MAX_DROPPED, MAX_PENDING},
+};
+use crate::{
+    clock::{self, communication::Message as ClockMessage},
+    simulation::{
+        self,
+        arena::Rule,
+        communication::{Message as SimulationMessage, Order},
+        token, FlockId,
+    },
+};
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
 
-use self::communication::Message as WsMessage;
-use crate::simulation::communication::Message as SimulationMessage;
+/// Most boids a single spawn command may ask for.
+const MAX_SPAWN: usize = 1000;
 
 pub struct WebSocketUpdate {
     socket_address: String,
+    admin_token: String,
+    keyframe_interval: u64,
+    max_pending: u64,
+    max_dropped: u64,
+    reply_timeout: Duration,
 }
 
 impl WebSocketUpdate {
@@ -22,54 +63,90 @@ impl WebSocketUpdate {
     {
         Self {
             socket_address: socket_address.into(),
+            admin_token: token::generate(),
+            keyframe_interval: KEYFRAME_INTERVAL,
+            max_pending: MAX_PENDING,
+            max_dropped: MAX_DROPPED,
+            reply_timeout: REPLY_TIMEOUT,
         }
     }
 
-    pub fn dispatch(&self, tx: Sender<SimulationMessage>, rx: Receiver<WsMessage>) {
-        if let Ok(web_socket) = WebSocket::new(|out: ws::Sender| {
-            let simulation_tx = tx.clone();
-            move |msg: Message| {
-                info!("Server got message '{}'. ", msg);
-                if let Ok(command_text) = msg.as_text() {
-                    if let Ok(command) = serde_json::from_str::<Command>(command_text) {
-                        match command {
-                            Command::Spawn { team } => {
-                                if simulation_tx
-                                    .send(SimulationMessage::Spawn((team, 5)))
-                                    .is_err()
-                                {
-                                    error!("could not send a spawn message");
-                                }
-                            }
-                        }
-                    } else {
-                        error!("could not serialize {}", msg);
-                    }
-                } else {
-                    error!("could not read '{}' as text", msg)
-                }
-                out.ping(vec![])
-            }
+    pub fn admin_token<S>(mut self, admin_token: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.admin_token = admin_token.into();
+        self
+    }
+
+    pub fn keyframe_interval(mut self, keyframe_interval: u64) -> Self {
+        self.keyframe_interval = keyframe_interval;
+        self
+    }
+
+    pub fn max_pending(mut self, max_pending: u64) -> Self {
+        self.max_pending = max_pending.max(1);
+        self
+    }
+
+    pub fn max_dropped(mut self, max_dropped: u64) -> Self {
+        self.max_dropped = max_dropped;
+        self
+    }
+
+    pub fn reply_timeout(mut self, reply_timeout: Duration) -> Self {
+        self.reply_timeout = reply_timeout;
+        self
+    }
+
+    pub fn dispatch(
+        &self,
+        tx: Sender<SimulationMessage>,
+        clock_tx: Sender<ClockMessage>,
+        rx: Receiver<WsMessage>,
+    ) {
+        let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
+        let factory_clients = clients.clone();
+        if let Ok(web_socket) = WebSocket::new(|out: ws::Sender| Connection {
+            out,
+            session: Session::default(),
+            encoding: Encoding::default(),
+            clients: factory_clients.clone(),
+            simulation_tx: tx.clone(),
+            clock_tx: clock_tx.clone(),
+            admin_token: self.admin_token.clone(),
+            keyframe_interval: self.keyframe_interval,
+            reply_timeout: self.reply_timeout,
         }) {
-            let sender = web_socket.broadcaster();
+            let (max_pending, max_dropped) = (self.max_pending, self.max_dropped);
+            let broadcaster = web_socket.broadcaster();
             let send_thread = thread::Builder::new()
                 .name("repeater".to_string())
-                .spawn(move || loop {
-                    match rx.recv() {
-                        Ok(message) => match message {
-                            WsMessage::Update(payload) => {
-                                sender.send(payload).unwrap();
-                            }
-                        },
+                .spawn(move || {
+                    loop {
+                        match rx.recv() {
+                            Ok(message) => match message {
+                                WsMessage::Update(update) => {
+                                    publish(&clients, &update, max_pending, max_dropped)
+                                }
+                                WsMessage::Events(events) => broadcast(&clients, &events),
+                                WsMessage::Stop => break,
+                            },
 
-                        Err(error) => {
-                            error!("could not receive message: {}", error);
+                            Err(error) => {
+                                error!("could not receive message: {}", error);
+                                break;
+                            }
                         }
                     }
+                    if let Err(error) = broadcaster.shutdown() {
+                        error!("could not shut down websocket: {}", error);
+                    }
                 })
                 .unwrap();
             if let Err(error) = web_socket.listen(&self.socket_address) {
                 error!("Websocket could not listen {:?}", error);
+                return;
             }
             send_thread.join().unwrap();
         } else {
@@ -78,7 +155,473 @@ impl WebSocketUpdate {
     }
 }
 
+type Clients = Arc<Mutex<HashMap<u32, Client>>>;
+
+struct Client {
+    out: ws::Sender,
+    session: Session,
+    encoding: Encoding,
+    stream: Stream,
+    flow: Flow,
+}
+
+fn lock(clients: &Clients) -> MutexGuard<'_, HashMap<u32, Client>> {
+    clients.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sends every client the frame of the update that matches its subscription.
+///
+/// A client with too many unacknowledged frames skips updates and resyncs with
+/// a keyframe of the latest state once it catches up. A client that keeps
+/// lagging is disconnected.
+fn publish(clients: &Clients, update: &Value, max_pending: u64, max_dropped: u64) {
+    let mut clients = lock(clients);
+    let mut lagging = Vec::new();
+    for (id, client) in clients.iter_mut() {
+        match client.flow.admit(max_pending, max_dropped) {
+            Admission::Send => {}
+            Admission::Drop => {
+                client.stream.resync();
+                continue;
+            }
+            Admission::Disconnect => {
+                lagging.push(*id);
+                continue;
+            }
+        }
+        if let Some(frame) = client.stream.frame(update) {
+            let sent = client
+                .encoding
+                .encode(&frame)
+                .and_then(|message| client.out.send(message).map_err(|error| error.to_string()))
+                .and_then(|_| {
+                    client
+                        .out
+                        .ping(client.flow.sent())
+                        .map_err(|error| error.to_string())
+                });
+            if let Err(error) = sent {
+                error!("could not send update to websocket client: {}", error);
+                client.stream.resync();
+            }
+        }
+    }
+    for id in lagging {
+        if let Some(client) = clients.remove(&id) {
+            warn!("disconnecting websocket client {} that lags behind", id);
+            if let Err(error) = client
+                .out
+                .close_with_reason(CloseCode::Policy, "lagging behind")
+            {
+                error!("could not disconnect websocket client {}: {}", id, error);
+            }
+        }
+    }
+}
+
+/// Sends every client the events it may see about the teams it subscribed to,
+/// encoding each selection of events once per encoding in use.
+fn broadcast(clients: &Clients, payload: &Value) {
+    let events = match payload.get("events").and_then(Value::as_array) {
+        Some(events) => events,
+        None => return,
+    };
+    let clients = lock(clients);
+    let mut encoded: HashMap<(Encoding, Vec<usize>), Message> = HashMap::new();
+    for client in clients.values().filter(|client| !client.flow.lagging()) {
+        let visible: Vec<usize> = (0..events.len())
+            .filter(|&index| {
+                client.session.may_see(&events[index]) && client.stream.follows(&events[index])
+            })
+            .collect();
+        if visible.is_empty() {
+            continue;
+        }
+        let message = match encoded.entry((client.encoding, visible)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                match client.encoding.encode(&select(payload, &entry.key().1)) {
+                    Ok(message) => entry.insert(message),
+                    Err(error) => {
+                        error!(
+                            "could not encode payload as {:?}: {}",
+                            client.encoding, error
+                        );
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Err(error) = client.out.send(message.clone()) {
+            error!("could not send to websocket client: {}", error);
+        }
+    }
+}
+
+/// The payload with only the events at the given indices.
+fn select(payload: &Value, indices: &[usize]) -> Value {
+    let mut selection = payload.clone();
+    if let Some(events) = selection.get_mut("events").and_then(Value::as_array_mut) {
+        *events = indices.iter().map(|&index| events[index].take()).collect();
+    }
+    selection
+}
+
+struct Connection {
+    out: ws::Sender,
+    session: Session,
+    encoding: Encoding,
+    clients: Clients,
+    simulation_tx: Sender<SimulationMessage>,
+    clock_tx: Sender<ClockMessage>,
+    admin_token: String,
+    keyframe_interval: u64,
+    reply_timeout: Duration,
+}
+
+impl Connection {
+    fn subscribe(&self, subscription: Subscription) -> Result<(), String> {
+        subscription.validate()?;
+        let mut clients = lock(&self.clients);
+        let client = clients
+            .get_mut(&self.out.connection_id())
+            .ok_or_else(|| "connection is not open".to_string())?;
+        client.stream = Stream::new(subscription, self.keyframe_interval);
+        Ok(())
+    }
+}
+
+impl Handler for Connection {
+    fn on_request(&mut self, request: &Request) -> ws::Result<Response> {
+        let mut response = Response::from_request(request)?;
+        let (encoding, protocol) = Encoding::negotiate(request);
+        if let Some(protocol) = protocol {
+            response.set_protocol(&protocol);
+        }
+        self.encoding = encoding;
+        Ok(response)
+    }
+
+    fn on_open(&mut self, _: WsHandshake) -> ws::Result<()> {
+        info!("websocket client connected with {:?}", self.encoding);
+        let client = Client {
+            out: self.out.clone(),
+            session: self.session.clone(),
+            encoding: self.encoding,
+            stream: Stream::new(Subscription::default(), self.keyframe_interval),
+            flow: Flow::default(),
+        };
+        lock(&self.clients).insert(self.out.connection_id(), client);
+        Ok(())
+    }
+
+    fn on_close(&mut self, _: ws::CloseCode, _: &str) {
+        lock(&self.clients).remove(&self.out.connection_id());
+    }
+
+    fn on_frame(&mut self, frame: Frame) -> ws::Result<Option<Frame>> {
+        if frame.opcode() == OpCode::Pong {
+            if let Some(client) = lock(&self.clients).get_mut(&self.out.connection_id()) {
+                client.flow.acknowledge(frame.payload());
+            }
+        }
+        Ok(Some(frame))
+    }
+
+    fn on_message(&mut self, msg: Message) -> ws::Result<()> {
+        let command = command_of(&msg);
+        if let Ok(command) = &command {
+            info!("websocket client sent {}", command.name());
+        }
+        let reply = match command {
+            Ok(Command::Authenticate(handshake)) => {
+                match authenticate(
+                    handshake,
+                    &self.simulation_tx,
+                    &self.admin_token,
+                    self.reply_timeout,
+                ) {
+                    Ok(authenticated) => {
+                        info!("websocket session authenticated as {:?}", authenticated);
+                        if let Some(client) = lock(&self.clients).get_mut(&self.out.connection_id())
+                        {
+                            client.session = authenticated.clone();
+                        }
+                        self.session = authenticated;
+                        Reply::Ack {
+                            command: "Authenticate".to_string(),
+                        }
+                    }
+                    Err(reason) => Reply::Error {
+                        command: Some("Authenticate".to_string()),
+                        reason,
+                    },
+                }
+            }
+            Ok(Command::Subscribe(subscription)) => match self.subscribe(subscription) {
+                Ok(()) => Reply::Ack {
+                    command: "Subscribe".to_string(),
+                },
+                Err(reason) => Reply::Error {
+                    command: Some("Subscribe".to_string()),
+                    reason,
+                },
+            },
+            Ok(command) => {
+                let name = command.name();
+                let result = self.session.authorize(&command).and_then(|_| {
+                    execute(
+                        command,
+                        &self.simulation_tx,
+                        &self.clock_tx,
+                        self.reply_timeout,
+                    )
+                });
+                match result {
+                    Ok(()) => Reply::Ack { command: name },
+                    Err(reason) => Reply::Error {
+                        command: Some(name),
+                        reason,
+                    },
+                }
+            }
+            Err(reason) => Reply::Error {
+                command: None,
+                reason,
+            },
+        };
+        match serde_json::to_value(&reply) {
+            Ok(reply) => match self.encoding.encode(&reply) {
+                Ok(message) => self.out.send(message),
+                Err(error) => {
+                    error!("could not encode reply: {}", error);
+                    Ok(())
+                }
+            },
+            Err(error) => {
+                error!("could not serialize reply: {}", error);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn command_of(msg: &Message) -> Result<Command, String> {
+    let command_text = msg.as_text().map_err(|_| {
+        error!("could not read command as text");
+        "command is not text".to_string()
+    })?;
+    serde_json::from_str::<Command>(command_text).map_err(|error| {
+        error!(
+            "could not deserialize command at line {} column {}",
+            error.line(),
+            error.column()
+        );
+        error.to_string()
+    })
+}
+
+fn authenticate(
+    handshake: Handshake,
+    simulation_tx: &Sender<SimulationMessage>,
+    admin_token: &str,
+    timeout: Duration,
+) -> Result<Session, String> {
+    match (handshake.role, handshake.team, handshake.token) {
+        (Role::Spectator, _, _) => Ok(Session::Spectator),
+        (Role::Admin, _, Some(token)) if token::matches(admin_token, &token) => Ok(Session::Admin),
+        (Role::Team, Some(team), Some(token)) => {
+            let (reply_tx, reply_rx) = channel();
+            tell(
+                simulation_tx,
+                SimulationMessage::Authenticate(team.clone(), token, reply_tx),
+            )?;
+            match reply_rx.recv_timeout(timeout) {
+                Ok(true) => Ok(Session::Team(team)),
+                Ok(false) => Err(format!("invalid token for team \"{}\"", team)),
+                Err(error) => Err(format!("did not receive reply from simulation: {}", error)),
+            }
+        }
+        _ => Err("invalid credentials".to_string()),
+    }
+}
+
+fn execute(
+    command: Command,
+    simulation_tx: &Sender<SimulationMessage>,
+    clock_tx: &Sender<ClockMessage>,
+    timeout: Duration,
+) -> Result<(), String> {
+    let order = match command {
+        Command::Pause => return tell(clock_tx, ClockMessage::Pause),
+        Command::Resume => return tell(clock_tx, ClockMessage::Resume),
+        Command::SetTick { milliseconds } => {
+            if !(1..=clock::MAX_TICK).contains(&milliseconds) {
+                return Err(format!(
+                    "tick should be between 1 and {} milliseconds",
+                    clock::MAX_TICK
+                ));
+            }
+            return tell(
+                clock_tx,
+                ClockMessage::SetTick(Duration::from_millis(milliseconds)),
+            );
+        }
+        Command::Spawn { count, .. } | Command::SpawnAll { count } if count > MAX_SPAWN => {
+            return Err(format!("count should be at most {}", MAX_SPAWN));
+        }
+        Command::Spawn {
+            team,
+            count,
+            position,
+        } => Order::Spawn {
+            team,
+            count,
+            position,
+        },
+        Command::SpawnAll { count } => Order::SpawnAll { count },
+        Command::Kill { team, boids } => Order::Kill { team, boids },
+        Command::Clear { team } => Order::Clear { team },
+        Command::Toggle { rule, enabled } => Order::Toggle { rule, enabled },
+        Command::Assign { team, role } => Order::Assign { team, role },
+        Command::Authenticate(_) | Command::Subscribe(_) => {
+            return Err("already handled".to_string())
+        }
+    };
+
+    let (reply_tx, reply_rx) = channel();
+    tell(simulation_tx, SimulationMessage::Order(order, reply_tx))?;
+    reply_rx
+        .recv_timeout(timeout)
+        .map_err(|error| format!("did not receive reply from simulation: {}", error))?
+}
+
+fn tell<M>(tx: &Sender<M>, message: M) -> Result<(), String> {
+    tx.send(message)
+        .map_err(|_| "could not reach the server".to_string())
+}
+
 #[derive(Deserialize, Debug)]
 pub enum Command {
-    Spawn { team: String },
+    Authenticate(Handshake),
+    Subscribe(Subscription),
+    Spawn {
+        team: String,
+        #[serde(default = "default_count")]
+        count: usize,
+        #[serde(default)]
+        position: Option<(f64, f64)>,
+    },
+    SpawnAll {
+        #[serde(default = "default_count")]
+        count: usize,
+    },
+    Kill {
+        team: String,
+        boids: Vec<FlockId>,
+    },
+    Clear {
+        team: String,
+    },
+    Pause,
+    Resume,
+    SetTick {
+        milliseconds: u64,
+    },
+    Toggle {
+        rule: Rule,
+        enabled: bool,
+    },
+    Assign {
+        team: String,
+        role: simulation::Role,
+    },
+}
+
+fn default_count() -> usize {
+    5
+}
+
+impl Command {
+    fn name(&self) -> String {
+        match self {
+            Command::Spawn { .. } => "Spawn",
+            Command::SpawnAll { .. } => "SpawnAll",
+            Command::Kill { .. } => "Kill",
+            Command::Clear { .. } => "Clear",
+            Command::Pause => "Pause",
+            Command::Resume => "Resume",
+            Command::SetTick { .. } => "SetTick",
+            Command::Toggle { .. } => "Toggle",
+            Command::Assign { .. } => "Assign",
+            Command::Authenticate(_) => "Authenticate",
+            Command::Subscribe(_) => "Subscribe",
+        }
+        .to_string()
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub enum Reply {
+    Ack {
+        command: String,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+        reason: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_should_be_deserialized_with_defaults() {
+        let command: Command = serde_json::from_str(r#"{"Spawn": {"team": "TEST"}}"#).unwrap();
+        match command {
+            Command::Spawn {
+                team,
+                count,
+                position,
+            } => {
+                assert_eq!(team, "TEST");
+                assert_eq!(count, 5);
+                assert_eq!(position, None);
+            }
+            _ => panic!("expected a spawn command"),
+        }
+
+        let command: Command = serde_json::from_str(r#""Pause""#).unwrap();
+        assert_eq!(command.name(), "Pause");
+
+        let (clock_tx, _clock_rx) = channel();
+        let (simulation_tx, _simulation_rx) = channel();
+        assert!(execute(
+            Command::SetTick { milliseconds: 0 },
+            &simulation_tx,
+            &clock_tx,
+            Duration::from_secs(1)
+        )
+        .is_err());
+        assert!(execute(
+            Command::SetTick {
+                milliseconds: clock::MAX_TICK + 1
+            },
+            &simulation_tx,
+            &clock_tx,
+            Duration::from_secs(1)
+        )
+        .is_err());
+        assert!(execute(
+            Command::SpawnAll {
+                count: 1_000_000_000
+            },
+            &simulation_tx,
+            &clock_tx,
+            Duration::from_secs(1)
+        )
+        .is_err());
+    }
 }
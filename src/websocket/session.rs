@@ -0,0 +1,102 @@
+use serde_json::Value;
+
+use super::Command;
+
+#[derive(Deserialize, Debug)]
+pub struct Handshake {
+    pub role: Role,
+    #[serde(default)]
+    pub team: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Spectator,
+    Team,
+    Admin,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Session {
+    #[default]
+    Spectator,
+    Team(String),
+    Admin,
+}
+
+impl Session {
+    pub fn authorize(&self, command: &Command) -> Result<(), String> {
+        match (self, command) {
+            (Session::Admin, _) => Ok(()),
+            (Session::Team(own), Command::Spawn { team, .. })
+            | (Session::Team(own), Command::Kill { team, .. })
+            | (Session::Team(own), Command::Clear { team })
+                if own == team =>
+            {
+                Ok(())
+            }
+            (Session::Team(own), _) => {
+                Err(format!("team {} is not allowed to {}", own, command.name()))
+            }
+            (Session::Spectator, _) => {
+                Err(format!("spectators are not allowed to {}", command.name()))
+            }
+        }
+    }
+
+    /// Brain errors only go to admins and to the team whose brain failed.
+    pub fn may_see(&self, event: &Value) -> bool {
+        match (self, event.get("BrainError")) {
+            (_, None) | (Session::Admin, _) => true,
+            (Session::Team(own), Some(error)) => {
+                error.get("team").and_then(Value::as_str) == Some(own.as_str())
+            }
+            (Session::Spectator, Some(_)) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn teams_should_only_act_on_their_own_team() {
+        let session = Session::Team("TEST".to_owned());
+        let own = Command::Clear {
+            team: "TEST".to_owned(),
+        };
+        let rival = Command::Spawn {
+            team: "RIVAL".to_owned(),
+            count: 500,
+            position: None,
+        };
+
+        assert!(session.authorize(&own).is_ok());
+        assert!(session.authorize(&rival).is_err());
+        assert!(session.authorize(&Command::Pause).is_err());
+        assert!(session
+            .authorize(&Command::Assign {
+                team: "TEST".to_owned(),
+                role: crate::simulation::Role::Predator,
+            })
+            .is_err());
+        assert!(Session::Spectator.authorize(&own).is_err());
+        assert!(Session::Admin.authorize(&rival).is_ok());
+    }
+
+    #[test]
+    fn brain_errors_should_only_be_seen_by_their_team_and_admins() {
+        let error = json!({"BrainError": {"team": "TEST", "diagnostic": {}}});
+        let capture = json!({"Capture": {"predator": "RIVAL", "prey": "TEST", "boid": 1}});
+
+        assert!(Session::Team("TEST".to_owned()).may_see(&error));
+        assert!(!Session::Team("RIVAL".to_owned()).may_see(&error));
+        assert!(!Session::Spectator.may_see(&error));
+        assert!(Session::Admin.may_see(&error));
+        assert!(Session::Spectator.may_see(&capture));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use super::delta::Compressor;
+
+/// Fewest and most updates per second a client can subscribe to.
+const MIN_RATE: f64 = 0.1;
+const MAX_RATE: f64 = 120f64;
+
+/// The fields of an event that name a team.
+const TEAM_FIELDS: [&str; 6] = ["team", "predator", "prey", "other_team", "by", "flag"];
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Subscription {
+    #[serde(default)]
+    pub teams: Option<Vec<String>>,
+    #[serde(default)]
+    pub rate: Option<f64>,
+    #[serde(default = "all_boids")]
+    pub boids: bool,
+}
+
+fn all_boids() -> bool {
+    true
+}
+
+impl Default for Subscription {
+    fn default() -> Self {
+        Self {
+            teams: None,
+            rate: None,
+            boids: all_boids(),
+        }
+    }
+}
+
+impl Subscription {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.rate {
+            Some(rate) if !(MIN_RATE..=MAX_RATE).contains(&rate) => Err(format!(
+                "rate should be between {} and {} updates per second",
+                MIN_RATE, MAX_RATE
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the event involves a subscribed team, or no team at all.
+    fn follows(&self, event: &Value) -> bool {
+        let subscribed = match &self.teams {
+            Some(subscribed) => subscribed,
+            None => return true,
+        };
+        let teams: Vec<&str> = event
+            .as_object()
+            .into_iter()
+            .flat_map(|event| event.values())
+            .flat_map(|fields| {
+                TEAM_FIELDS
+                    .iter()
+                    .filter_map(move |field| fields.get(field))
+            })
+            .filter_map(Value::as_str)
+            .collect();
+        teams.is_empty()
+            || teams
+                .iter()
+                .any(|team| subscribed.iter().any(|name| name == team))
+    }
+
+    fn filter(&self, update: &Value) -> Value {
+        let mut update = update.clone();
+        if let Some(teams) = update.get_mut("teams").and_then(Value::as_object_mut) {
+            if let Some(subscribed) = &self.teams {
+                *teams = mem::take(teams)
+                    .into_iter()
+                    .filter(|(name, _)| subscribed.contains(name))
+                    .collect();
+            }
+            if !self.boids {
+                for team in teams.values_mut().filter_map(Value::as_object_mut) {
+                    team.remove("flock");
+                }
+            }
+        }
+        update
+    }
+}
+
+/// The updates of a single client, filtered, downsampled and delta-compressed.
+pub struct Stream {
+    subscription: Subscription,
+    compressor: Compressor,
+    sent: Option<Instant>,
+}
+
+impl Stream {
+    pub fn new(subscription: Subscription, keyframe_interval: u64) -> Self {
+        Self {
+            subscription,
+            compressor: Compressor::new(keyframe_interval),
+            sent: None,
+        }
+    }
+
+    pub fn frame(&mut self, update: &Value) -> Option<Value> {
+        let now = Instant::now();
+        let interval = self
+            .subscription
+            .rate
+            .map(|rate| Duration::from_secs_f64(1f64 / rate));
+        if let (Some(sent), Some(interval)) = (self.sent, interval) {
+            if now.duration_since(sent) < interval {
+                return None;
+            }
+        }
+        self.sent = Some(now);
+        self.compressor.compress(self.subscription.filter(update))
+    }
+
+    pub fn follows(&self, event: &Value) -> bool {
+        self.subscription.follows(event)
+    }
+
+    pub fn resync(&mut self) {
+        self.compressor.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn streams_should_only_carry_subscribed_teams() {
+        let subscription: Subscription =
+            serde_json::from_str(r#"{"teams": ["blue"], "boids": false}"#).unwrap();
+        let mut stream = Stream::new(subscription, 10);
+        let update = json!({"teams": {
+            "blue": {"name": "blue", "flock": {"boids": {"1": {"x": 0.1, "y": 0.1}}}},
+            "red": {"name": "red", "flock": {"boids": {"2": {"x": 0.2, "y": 0.2}}}}
+        }});
+
+        let frame = stream.frame(&update).unwrap();
+
+        assert_eq!(
+            frame,
+            json!({"teams": {"blue": {"name": "blue"}}, "keyframe": true})
+        );
+        assert!(stream.follows(&json!({"Capture": {"predator": "red", "prey": "blue"}})));
+        assert!(!stream.follows(&json!({"Death": {"team": "red", "boid": 2}})));
+        assert!(Subscription::default().follows(&json!({"Death": {"team": "red"}})));
+        assert!(Subscription {
+            rate: Some(0f64),
+            ..Subscription::default()
+        }
+        .validate()
+        .is_err());
+        assert!(Subscription {
+            rate: Some(1e-20),
+            ..Subscription::default()
+        }
+        .validate()
+        .is_err());
+        assert!(Subscription {
+            rate: Some(f64::NAN),
+            ..Subscription::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -1,4 +1,4 @@
-(function(document, Elm, socket_host, socket_port) {
+(function(document, Elm, location) {
     const container = document.getElementById('client-container');
     const app = Elm.Boiding.init({
         node: container,
@@ -7,13 +7,77 @@
 
     window.app = app;
 
-    const socket_address = `ws://${socket_host}:${socket_port}`;
-    const socket = new WebSocket(socket_address);
-    socket.addEventListener('message', function(event){
-        app.ports.updateTeams.send(event.data);
-    })
+    const parameters = new URLSearchParams(location.search);
+    let state = null;
 
-    app.ports.spawn.subscribe(function(team){
-        socket.send(JSON.stringify({'Spawn': {'team': team}}));
-    })
-})(document, Elm, location.hostname, 3435);
+    fetch('/config')
+        .then(function(response){ return response.json(); })
+        .then(function(config){ connect(socket_address(config.websocket)); });
+
+    function socket_address(websocket) {
+        const protocol = location.protocol === 'https:' ? 'wss:' : 'ws:';
+        if ('Path' in websocket) {
+            return `${protocol}//${location.host}${websocket.Path}`;
+        }
+        return `${protocol}//${location.hostname}:${websocket.Port}`;
+    }
+
+    function connect(socket_address) {
+        const socket = new WebSocket(socket_address);
+        socket.addEventListener('open', function(){
+            const token = parameters.get('token');
+            if (token) {
+                const team = parameters.get('team');
+                const role = team ? 'Team' : 'Admin';
+                socket.send(JSON.stringify({'Authenticate': {'role': role, 'team': team, 'token': token}}));
+            }
+        })
+        socket.addEventListener('message', function(event){
+            const message = JSON.parse(event.data);
+            if ('keyframe' in message) {
+                delete message.keyframe;
+                state = message;
+                app.ports.updateTeams.send(JSON.stringify(state));
+            } else if ('delta' in message) {
+                if (state) {
+                    apply(state, message.delta);
+                    app.ports.updateTeams.send(JSON.stringify(state));
+                }
+            } else if ('events' in message) {
+                message.events.forEach(function(e){ console.log(e); });
+            } else if ('Ack' in message || 'Error' in message) {
+                console.log(message);
+            }
+        })
+
+        app.ports.spawn.subscribe(function(team){
+            socket.send(JSON.stringify({'Spawn': {'team': team}}));
+        })
+    }
+
+    function apply(state, delta) {
+        Object.entries(delta.teams).forEach(function([name, change]){
+            const team = state.teams[name] || (state.teams[name] = {'flock': {'boids': {}}});
+            Object.assign(team, change.fields);
+            Object.entries(change.boids).forEach(function([id, fields]){
+                const boid = team.flock.boids[id] || (team.flock.boids[id] = {});
+                Object.entries(fields).forEach(function([key, value]){
+                    if (value === null) {
+                        delete boid[key];
+                    } else {
+                        boid[key] = value;
+                    }
+                });
+            });
+            change.removed.forEach(function(id){ delete team.flock.boids[id]; });
+        });
+        delta.removed_teams.forEach(function(name){ delete state.teams[name]; });
+        Object.entries(delta.world).forEach(function([key, value]){
+            if (value === null) {
+                delete state[key];
+            } else {
+                state[key] = value;
+            }
+        });
+    }
+})(document, Elm, location);